@@ -1,12 +1,74 @@
-use wood::message::{create_plain_message_chain, ChatroomType};
-use wood::Bot;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use wood::command::{Command, CommandConfig};
+use wood::message::{create_plain_message_chain, ChatroomType, MessageChain};
+use wood::{Bot, BoxFuture, Context, Next};
+
+// Log the time spent on every event.
+fn timer<'a>(ctx: Context, next: Next<'a>) -> BoxFuture<'a> {
+    Box::pin(async move {
+        let start = std::time::Instant::now();
+        next.run(ctx).await?;
+        println!("Handled in {:?}", start.elapsed());
+
+        Ok(())
+    })
+}
+
+// Commands with metadata are listed by `/help`,
+// and the usage is replied automatically when the arguments are invalid.
+#[wood::command(name = "repeat", aliases = ["r"], desc = "Repeat the text for some times.")]
+async fn repeat(ctx: Context, (times, text): (usize, String)) -> wood::Result<()> {
+    let text = vec![text; times.min(5)].join("\n");
+    ctx.reply(create_plain_message_chain(text)).await?;
+
+    Ok(())
+}
+
+#[wood::command(name = "add", desc = "Add a todo.")]
+async fn todo_add(ctx: Context, (text,): (String,)) -> wood::Result<()> {
+    ctx.reply(create_plain_message_chain(format!("Added: {}", text)))
+        .await?;
+
+    Ok(())
+}
+
+#[wood::command(name = "done", desc = "Finish a todo.")]
+async fn todo_done(ctx: Context, (index,): (usize,)) -> wood::Result<()> {
+    ctx.reply(create_plain_message_chain(format!("Done: #{}", index)))
+        .await?;
+
+    Ok(())
+}
 
 #[tokio::main]
 async fn main() {
-    let (config, session, base_url) = wood::init("config/config.yml").await;
+    let (config, session, base_url) = match wood::init("config/config.yml").await {
+        Ok(init) => init,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
     let mut bot = Bot::new(config, &session, &base_url);
 
-    bot.on("message", &|ctx| async move {
+    // Handle at most 8 events at the same time, and keep the order in each chat.
+    bot.set_concurrency(8);
+    bot.set_ordered(true);
+
+    bot.use_middleware(timer);
+
+    // Errors of the handlers are logged, and the user gets this reply.
+    bot.set_error_reply(Some("Oops, something went wrong."));
+
+    // Commands start with `/` or `!`, and `wood, /help` works like an at in groups.
+    bot.set_command_config(
+        CommandConfig::new()
+            .prefixes(&["/", "!"])
+            .nickname("wood")
+            .case_sensitive(false),
+    );
+
+    bot.on("message", |ctx| async move {
         match ctx.chatroom_type() {
             ChatroomType::Friend => println!(
                 "Received friend message from {}({})",
@@ -21,36 +83,89 @@ async fn main() {
                 ctx.chatroom_name(),
                 ctx.chatroom_id()
             ),
+
+            ChatroomType::Temp => println!(
+                "Received temp message from {}[{}] through group: {}[{}]",
+                ctx.sender_nickname(),
+                ctx.sender_id(),
+                ctx.chatroom_name(),
+                ctx.chatroom_id()
+            ),
+
+            ChatroomType::Stranger => println!(
+                "Received stranger message from {}({})",
+                ctx.sender_nickname(),
+                ctx.sender_id()
+            ),
         }
 
         Ok(())
     });
 
-    bot.on("message", &|ctx| async move {
+    bot.on("message", |ctx| async move {
         println!("{:#?}", ctx.message_chain());
 
         Ok(())
     });
 
+    // The spam filter runs before the other listeners, and stops them.
+    bot.on_with_priority("groupMessage", 100, |ctx| async move {
+        if ctx.message_chain().plain_text().contains("spam") {
+            ctx.stop_propagation();
+        }
+
+        Ok(())
+    });
+
     // Only echo message when received friend message
-    bot.on("friendMessage", &|ctx| async move {
+    bot.on("friendMessage", |ctx| async move {
         ctx.reply(ctx.message_chain()).await?;
 
         Ok(())
     });
 
     // No need to use `move` when regardless of context
-    bot.on("groupMessage", &|_| async { Ok(()) });
+    bot.on("groupMessage", |_| async { Ok(()) });
+
+    // Listen to mirai events other than messages.
+    bot.on("memberJoin", |ctx| async move {
+        let text = format!("Welcome, {}!", ctx.sender_nickname());
+        ctx.reply(create_plain_message_chain(text)).await?;
+
+        Ok(())
+    });
+
+    // Nudge back when the bot is nudged.
+    bot.on("nudge", |ctx| async move {
+        if ctx.is_nudging_me() {
+            ctx.nudge_sender().await?;
+        }
+
+        Ok(())
+    });
+
+    // Accept the friend requests with the right answer.
+    bot.on("newFriendRequest", |ctx| async move {
+        if let wood::event::Event::NewFriendRequestEvent { message, .. } = ctx.event() {
+            if message.contains("wood") {
+                ctx.accept().await?;
+            } else {
+                ctx.reject("Wrong answer.", false).await?;
+            }
+        }
+
+        Ok(())
+    });
 
     // You'll see a error message that tells that
     // you are listening a `InvalidEvent`.
-    bot.on("msg", &|_| async { Ok(()) });
+    bot.on("msg", |_| async { Ok(()) });
 
-    bot.on("command", &|ctx| async move {
+    bot.on("command", |ctx| async move {
         let command_name = ctx.command_name();
 
-        let text = if command_name == "" {
-            format!("Received empty command.")
+        let text = if command_name.is_empty() {
+            "Received empty command.".to_string()
         } else {
             format!("Received command: {}", ctx.command_name())
         };
@@ -61,13 +176,69 @@ async fn main() {
         Ok(())
     });
 
-    bot.command("hi", &|ctx| async move {
-        let message_chain = create_plain_message_chain("hi".to_string());
+    // Share a counter with the handlers, which can also be captured by the closure.
+    bot.manage(AtomicUsize::new(0));
+    bot.command("count", |ctx| async move {
+        let count = ctx.state::<AtomicUsize>()?.fetch_add(1, Ordering::SeqCst) + 1;
+        ctx.reply(create_plain_message_chain(format!("Count: {}", count)))
+            .await?;
+
+        Ok(())
+    });
+
+    bot.command("hi", |ctx| async move {
+        // Build a message chain with the builder.
+        let message_chain = MessageChain::new().text("hi ").face(1);
         ctx.reply(message_chain).await?;
 
         Ok(())
     });
 
+    // Try `/say --times=3 "hello world"`.
+    bot.command("say", |ctx| async move {
+        let args = ctx.args()?;
+
+        let text = match args.parse_into::<(String,)>() {
+            Ok((text,)) => text,
+            // Tell the user how to use the command.
+            Err(e) => {
+                ctx.reply(create_plain_message_chain(e.to_string())).await?;
+                return Ok(());
+            }
+        };
+        let times = args.option::<usize>("times")?.unwrap_or(1).min(5);
+
+        ctx.reply(create_plain_message_chain(vec![text; times].join("\n")))
+            .await?;
+
+        Ok(())
+    });
+
+    bot.register(repeat());
+
+    // Try `/todo add milk`, `/todo done 1` or `/help todo`.
+    bot.register(
+        Command::group("todo")
+            .desc("Manage the todo list.")
+            .subcommand(todo_add())
+            .subcommand(todo_done()),
+    );
+
+    // Reply to a message with `/what` to see the quoted message.
+    bot.command("what", |ctx| async move {
+        let text = match ctx.quoted_message().await? {
+            Some(event) => match event.message_chain() {
+                Some(message_chain) => format!("You quoted: {}", message_chain.plain_text()),
+                None => "You quoted something that is not a message.".to_string(),
+            },
+            None => "Nothing is quoted.".to_string(),
+        };
+
+        ctx.reply(create_plain_message_chain(text)).await?;
+
+        Ok(())
+    });
+
     // Start your bot with a callback.
     bot.start_with_callback(|bot| async {
         println!("Bot qq is: {}", bot.qq());
@@ -3,10 +3,16 @@ use wood::Bot;
 
 #[tokio::main]
 async fn main() {
-    let (config, session, base_url) = wood::init("config/config.yml").await;
+    let (config, session, base_url) = match wood::init("config/config.yml").await {
+        Ok(init) => init,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
     let mut bot = Bot::new(config, &session, &base_url);
 
-    bot.command("echo", &|ctx| async move {
+    bot.command("echo", |ctx| async move {
         ctx.reply(ctx.message_chain()).await?;
 
         Ok(())
@@ -0,0 +1,23 @@
+pub(crate) mod ws;
+pub(crate) use ws::WsClient;
+
+mod webhook;
+pub(crate) use webhook::{WebhookRequest, WebhookServer};
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub enum Adapter {
+    // Poll `/fetchMessage` of the http adapter.
+    #[default]
+    Http,
+
+    // Receive pushed messages from the ws adapter,
+    // the url looks like `ws://host:port/all?verifyKey=xxx&qq=xxx`.
+    Ws(String),
+
+    // Run a http server at `address` to receive the events posted by the webhook adapter.
+    // If `reply` is true, the first reply of an event is sent back in the response body.
+    Webhook {
+        address: String,
+        reply: bool,
+    },
+}
@@ -0,0 +1,220 @@
+use serde_json::Value;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+
+use crate::error::Error;
+use crate::Result;
+
+// Requests larger than this are not events from mirai.
+const MAX_REQUEST_SIZE: usize = 16 * 1024 * 1024;
+
+// A stalled connection should not block the following events.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct WebhookServer {
+    listener: TcpListener,
+}
+
+// A POST from mirai, which must be responded exactly once.
+pub struct WebhookRequest {
+    stream: TcpStream,
+    body: Value,
+}
+
+impl WebhookServer {
+    pub async fn bind(address: &str) -> Result<Self> {
+        let listener = TcpListener::bind(address).await?;
+
+        Ok(WebhookServer { listener })
+    }
+
+    // Accept the connections, and read each request in its own task,
+    // so that a stalled connection only times out itself.
+    // The requests are yielded in the order they are read.
+    pub fn incoming(self) -> mpsc::Receiver<WebhookRequest> {
+        let (requests, requests_rx) = mpsc::channel(1);
+
+        tokio::spawn(async move {
+            loop {
+                let stream = match self.listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        eprintln!("[Error] Receiving webhook request.\n{}", Error::from(e));
+                        continue;
+                    }
+                };
+
+                let requests = requests.clone();
+                tokio::spawn(async move {
+                    let req = match timeout(READ_TIMEOUT, read_request(stream)).await {
+                        Ok(req) => req,
+                        Err(_) => Err(Error::new("Timeout while reading the webhook request.")),
+                    };
+
+                    match req {
+                        Ok(req) => {
+                            let _ = requests.send(req).await;
+                        }
+                        Err(e) => eprintln!("[Error] Receiving webhook request.\n{}", e),
+                    }
+                });
+            }
+        });
+
+        requests_rx
+    }
+}
+
+async fn read_request(mut stream: TcpStream) -> Result<WebhookRequest> {
+    let mut buf = vec![];
+    let mut chunk = [0; 4096];
+
+    let (header_len, content_len) = loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(Error::new("The webhook connection is closed."));
+        }
+        buf.extend_from_slice(&chunk[..n]);
+
+        if let Some(lens) = parse_request(&buf)? {
+            break lens;
+        }
+    };
+
+    while buf.len() < header_len + content_len {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(Error::new("The webhook connection is closed."));
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let body = serde_json::from_slice(&buf[header_len..header_len + content_len])?;
+
+    Ok(WebhookRequest { stream, body })
+}
+
+impl WebhookRequest {
+    pub fn body(&self) -> &Value {
+        &self.body
+    }
+
+    // Respond with a command which will be executed by mirai,
+    // or with an empty body when there is nothing to reply.
+    pub async fn respond(mut self, command: Option<Value>) -> Result<()> {
+        let body = match command {
+            Some(command) => serde_json::to_string(&command)?,
+            None => String::new(),
+        };
+
+        let resp = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+
+        self.stream.write_all(resp.as_bytes()).await?;
+        self.stream.shutdown().await?;
+
+        Ok(())
+    }
+}
+
+// Return the length of the headers and the body,
+// or `None` if the headers are not complete yet.
+fn parse_request(buf: &[u8]) -> Result<Option<(usize, usize)>> {
+    let mut headers = [httparse::EMPTY_HEADER; 32];
+    let mut req = httparse::Request::new(&mut headers);
+
+    let header_len = match req.parse(buf) {
+        Ok(httparse::Status::Complete(len)) => len,
+        Ok(httparse::Status::Partial) => return Ok(None),
+        Err(e) => return Err(Error::new(&format!("Invalid webhook request: {}", e))),
+    };
+
+    if req.method != Some("POST") {
+        return Err(Error::new("Invalid webhook request: expected `POST`."));
+    }
+
+    let content_len = req
+        .headers
+        .iter()
+        .find(|header| header.name.eq_ignore_ascii_case("Content-Length"))
+        .and_then(|header| std::str::from_utf8(header.value).ok())
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+
+    if content_len > MAX_REQUEST_SIZE {
+        return Err(Error::new(
+            "Invalid webhook request: the body is too large.",
+        ));
+    }
+
+    Ok(Some((header_len, content_len)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_request, WebhookServer};
+    use serde_json::json;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tokio::time::timeout;
+
+    #[test]
+    fn check_parse_request() {
+        let req = b"POST / HTTP/1.1\r\nHost: localhost\r\ncontent-length: 2\r\n\r\n{}";
+        assert_eq!(parse_request(req).unwrap(), Some((req.len() - 2, 2)));
+
+        assert_eq!(
+            parse_request(b"POST / HTTP/1.1\r\nHost: loc").unwrap(),
+            None
+        );
+        assert!(parse_request(b"GET / HTTP/1.1\r\n\r\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn receive_event_and_respond() {
+        let server = WebhookServer::bind("127.0.0.1:0").await.unwrap();
+        let address = server.listener.local_addr().unwrap();
+
+        // a connection which never sends its request
+        let stalled = TcpStream::connect(address).await.unwrap();
+        let mut requests = server.incoming();
+
+        let client = tokio::spawn(async move {
+            let body = r#"{"type":"FriendMessage"}"#;
+            let mut stream = TcpStream::connect(address).await.unwrap();
+            let req = format!(
+                "POST / HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(req.as_bytes()).await.unwrap();
+
+            let mut resp = String::new();
+            stream.read_to_string(&mut resp).await.unwrap();
+            resp
+        });
+
+        // the stalled connection doesn't block the request
+        let req = timeout(Duration::from_secs(1), requests.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(req.body()["type"], "FriendMessage");
+        req.respond(Some(json!({"command": "sendFriendMessage"})))
+            .await
+            .unwrap();
+
+        let resp = client.await.unwrap();
+        assert!(resp.starts_with("HTTP/1.1 200 OK"));
+        assert!(resp.ends_with(r#"{"command":"sendFriendMessage"}"#));
+
+        drop(stalled);
+    }
+}
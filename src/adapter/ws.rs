@@ -0,0 +1,189 @@
+use futures_util::{SinkExt, StreamExt};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, oneshot};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+use crate::error::Error;
+use crate::Result;
+
+type PendingRequests = Arc<Mutex<HashMap<String, oneshot::Sender<Value>>>>;
+
+#[derive(Clone)]
+pub struct WsClient {
+    outgoing: mpsc::UnboundedSender<Message>,
+    pending: PendingRequests,
+    sync_id: Arc<AtomicI64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WsRequest<'a, P: Serialize> {
+    sync_id: String,
+    command: &'a str,
+    sub_command: Option<&'a str>,
+    content: &'a P,
+}
+
+impl WsClient {
+    // Connect to the ws adapter and reuse the session verified by http.
+    // The returned receiver yields every pushed message or event.
+    pub async fn connect(
+        url: &str,
+        session: &str,
+    ) -> Result<(Self, mpsc::UnboundedReceiver<Value>)> {
+        let url = format!("{}&sessionKey={}", url, session);
+        let (stream, _) = connect_async(url.as_str()).await?;
+        let (mut write, mut read) = stream.split();
+
+        let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<Message>();
+        let (events, events_rx) = mpsc::unbounded_channel();
+        let pending: PendingRequests = Arc::new(Mutex::new(HashMap::new()));
+
+        tokio::spawn(async move {
+            while let Some(message) = outgoing_rx.recv().await {
+                if let Err(e) = write.send(message).await {
+                    eprintln!("[Error] Sending websocket message.\n{}", Error::from(e));
+                    break;
+                }
+            }
+        });
+
+        let reader_pending = pending.clone();
+        tokio::spawn(async move {
+            while let Some(message) = read.next().await {
+                let text = match message {
+                    Ok(Message::Text(text)) => text,
+                    Ok(Message::Close(_)) => break,
+                    Ok(_) => continue,
+                    Err(e) => {
+                        eprintln!("[Error] Receiving websocket message.\n{}", Error::from(e));
+                        break;
+                    }
+                };
+
+                match serde_json::from_str::<Value>(&text) {
+                    Ok(value) => dispatch(&reader_pending, &events, value),
+                    Err(e) => eprintln!("[Error] Parsing websocket message.\n{}", Error::from(e)),
+                }
+            }
+
+            // Dropping the pending senders wakes up every waiting request.
+            reader_pending.lock().unwrap().clear();
+        });
+
+        Ok((
+            WsClient {
+                outgoing,
+                pending,
+                sync_id: Arc::new(AtomicI64::new(0)),
+            },
+            events_rx,
+        ))
+    }
+
+    pub async fn request<P: Serialize>(
+        &self,
+        command: &str,
+        sub_command: Option<&str>,
+        content: &P,
+    ) -> Result<Value> {
+        let sync_id = (self.sync_id.fetch_add(1, Ordering::Relaxed) + 1).to_string();
+
+        let request = WsRequest {
+            sync_id: sync_id.clone(),
+            command,
+            sub_command,
+            content,
+        };
+        let text = serde_json::to_string(&request)?;
+
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(sync_id.clone(), tx);
+
+        if self.outgoing.send(Message::Text(text)).is_err() {
+            self.pending.lock().unwrap().remove(&sync_id);
+            return Err(Error::new("The websocket connection is closed."));
+        }
+
+        rx.await
+            .map_err(|_| Error::new("The websocket connection is closed."))
+    }
+}
+
+// Responses are matched by `syncId` to the pending requests,
+// everything else that carries a `type` is a pushed message or event.
+fn dispatch(pending: &PendingRequests, events: &mpsc::UnboundedSender<Value>, mut value: Value) {
+    let sync_id = match &value["syncId"] {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        _ => String::new(),
+    };
+    let data = value["data"].take();
+
+    if let Some(tx) = pending.lock().unwrap().remove(&sync_id) {
+        let _ = tx.send(data);
+        return;
+    }
+
+    if data["type"].is_string() {
+        let _ = events.send(data);
+    }
+}
+
+// `/file/list` is sent as the command `file_list` through websocket.
+pub fn command_name(path: &str) -> String {
+    path.trim_start_matches('/').replace('/', "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{command_name, dispatch, PendingRequests};
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::{mpsc, oneshot};
+
+    #[test]
+    fn check_command_name() {
+        assert_eq!(command_name("/sendFriendMessage"), "sendFriendMessage");
+        assert_eq!(command_name("/file/list"), "file_list");
+        assert_eq!(
+            command_name("/resp/newFriendRequestEvent"),
+            "resp_newFriendRequestEvent"
+        );
+    }
+
+    #[tokio::test]
+    async fn dispatch_responses_and_events() {
+        let pending: PendingRequests = Arc::new(Mutex::new(HashMap::new()));
+        let (events, mut events_rx) = mpsc::unbounded_channel();
+
+        let (tx, rx) = oneshot::channel();
+        pending.lock().unwrap().insert("1".to_string(), tx);
+
+        dispatch(
+            &pending,
+            &events,
+            json!({"syncId": "1", "data": {"code": 0, "msg": "", "messageId": 7}}),
+        );
+        dispatch(
+            &pending,
+            &events,
+            json!({"syncId": "-1", "data": {"type": "FriendMessage", "messageChain": []}}),
+        );
+        // the handshake response carries an empty `syncId` and no `type`
+        dispatch(
+            &pending,
+            &events,
+            json!({"syncId": "", "data": {"code": 0, "session": "session"}}),
+        );
+
+        assert_eq!(rx.await.unwrap()["messageId"], 7);
+        assert_eq!(events_rx.recv().await.unwrap()["type"], "FriendMessage");
+        assert!(events_rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+
+use crate::message::sender::Group;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Announcement {
+    pub group: Group,
+    pub content: String,
+    pub sender_id: i64,
+    // the id used to delete the announcement
+    pub fid: String,
+    pub all_confirmed: bool,
+    pub confirmed_members_count: i64,
+    pub publication_time: i64,
+}
+
+// Options of publishing an announcement, use `..Default::default()` for the rest.
+//
+// At most one of `image_url`, `image_path` and `image_base64` should be set.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishOptions {
+    pub pinned: bool,
+    pub send_to_new_member: bool,
+    pub require_confirmation: bool,
+    pub show_edit_card: bool,
+    pub show_popup: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_base64: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Announcement, PublishOptions};
+    use crate::message::sender::{Group, Permission};
+    use serde_json::json;
+
+    #[test]
+    fn check_announcement_deserialize_result() {
+        let resp = json!({
+            "group": {"id": 20211113, "name": "木木", "permission": "ADMINISTRATOR"},
+            "content": "schedule",
+            "senderId": 20211114,
+            "fid": "fid",
+            "allConfirmed": false,
+            "confirmedMembersCount": 0,
+            "publicationTime": 20211113
+        });
+
+        assert_eq!(
+            serde_json::from_value::<Announcement>(resp).unwrap(),
+            Announcement {
+                group: Group {
+                    id: 20211113,
+                    name: "木木".to_string(),
+                    permission: Permission::ADMINISTRATOR,
+                },
+                content: "schedule".to_string(),
+                sender_id: 20211114,
+                fid: "fid".to_string(),
+                all_confirmed: false,
+                confirmed_members_count: 0,
+                publication_time: 20211113,
+            }
+        );
+    }
+
+    #[test]
+    fn check_publish_options_serialize_result() {
+        let options = PublishOptions {
+            pinned: true,
+            image_url: Some("https://mirai.host/schedule.png".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            serde_json::to_value(options).unwrap(),
+            json!({
+                "pinned": true,
+                "sendToNewMember": false,
+                "requireConfirmation": false,
+                "showEditCard": false,
+                "showPopup": false,
+                "imageUrl": "https://mirai.host/schedule.png"
+            })
+        );
+    }
+}
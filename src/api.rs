@@ -1,21 +1,85 @@
+use reqwest::multipart::{Form, Part};
+use reqwest::Method;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 
+use crate::adapter::{ws, WsClient};
+use crate::announcement::{Announcement, PublishOptions};
 use crate::error::Error;
-use crate::message::{ChatroomType, MessageChain, ReceivedMessage};
+use crate::event::{Event, RequestOperation, SubjectKind};
+use crate::files::GroupFiles;
+use crate::message::media::{content_hash, ImageId, MediaSource, UploadCache, VideoId, VoiceId};
+use crate::message::sender::{FriendSender, Group, GroupConfig, GroupSender, MemberInfo, Profile};
+use crate::message::{ChatroomType, MessageChain};
 use crate::Result;
 
+// Most bots only send a few memes anyway.
+const MAX_UPLOAD_CACHE_SIZE: usize = 1024;
+
+// The api, the upload type and the hashes of the files.
+type UploadCacheKey = (&'static str, &'static str, Vec<[u8; 32]>);
+
+// mirai can mute a member for 30 days at most.
+const MAX_MUTE_DURATION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
 #[derive(Deserialize, Debug)]
 struct BasicResponse {
     code: i32,
     msg: String,
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SendMessageParams {
+    session_key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<String>,
+    // `qq` and `group` are only for temp messages
+    #[serde(skip_serializing_if = "Option::is_none")]
+    qq: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<i64>,
+    message_chain: MessageChain,
+    quote: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionParams {
+    session_key: String,
+}
+
+// Params of the apis operating a group, a friend or a user.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TargetParams {
+    session_key: String,
+    target: i64,
+}
+
+// Params of the apis operating a member of a group.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MemberParams {
+    session_key: String,
+    target: i64,
+    member_id: i64,
+}
+
 #[derive(Clone)]
 pub struct Api {
     qq: i64,
     session: String,
     client: reqwest::Client,
     base_url: String,
+
+    // All the requests will be sent through websocket once connected.
+    ws: Arc<RwLock<Option<WsClient>>>,
+
+    // The ids of the uploaded files, so the same file is only uploaded once.
+    upload_cache: Arc<Mutex<UploadCache<UploadCacheKey>>>,
 }
 
 impl Api {
@@ -25,9 +89,11 @@ impl Api {
 
         Api {
             qq,
-            session: session.clone().to_string(),
+            session: session.to_string(),
             client,
             base_url: base_url.to_string(),
+            ws: Arc::new(RwLock::new(None)),
+            upload_cache: Arc::new(Mutex::new(UploadCache::new(MAX_UPLOAD_CACHE_SIZE))),
         }
     }
 
@@ -35,6 +101,65 @@ impl Api {
         self.base_url.clone() + path
     }
 
+    pub fn set_ws_client(&self, ws_client: Option<WsClient>) {
+        *self.ws.write().unwrap() = ws_client;
+    }
+
+    fn ws_client(&self) -> Option<WsClient> {
+        self.ws.read().unwrap().clone()
+    }
+
+    async fn request<P: Serialize>(&self, method: Method, path: &str, params: &P) -> Result<Value> {
+        let resp = match self.ws_client() {
+            Some(ws_client) => {
+                // Only `/groupConfig` and `/memberInfo` share the same path
+                // for getting and updating.
+                let sub_command = match path {
+                    "/groupConfig" | "/memberInfo" => match method {
+                        Method::GET => Some("get"),
+                        _ => Some("update"),
+                    },
+                    _ => None,
+                };
+
+                ws_client
+                    .request(&ws::command_name(path), sub_command, params)
+                    .await?
+            }
+            None => self.http_request(method, path, params).await?,
+        };
+
+        check_code(resp)
+    }
+
+    async fn http_request<P: Serialize>(
+        &self,
+        method: Method,
+        path: &str,
+        params: &P,
+    ) -> Result<Value> {
+        let builder = self.client.request(method.clone(), self.url(path));
+
+        let builder = match method {
+            Method::GET => builder.query(params),
+            _ => builder.json(params),
+        };
+
+        Ok(builder.send().await?.json::<Value>().await?)
+    }
+
+    pub(crate) async fn get<P: Serialize>(&self, path: &str, params: &P) -> Result<Value> {
+        self.request(Method::GET, path, params).await
+    }
+
+    pub(crate) async fn post<P: Serialize>(&self, path: &str, params: &P) -> Result<Value> {
+        self.request(Method::POST, path, params).await
+    }
+
+    pub(crate) fn session(&self) -> &str {
+        &self.session
+    }
+
     pub async fn link(&self) -> Result<()> {
         #[derive(Serialize)]
         #[serde(rename_all = "camelCase")]
@@ -48,6 +173,7 @@ impl Api {
             qq: self.qq,
         };
 
+        // Session management always goes through http.
         let resp = self
             .client
             .post(self.url("/bind"))
@@ -80,14 +206,12 @@ impl Api {
 
         let resp = self
             .client
-            .post(self.url("/bind"))
+            .post(self.url("/release"))
             .json(&params)
             .send()
-            .await
-            .unwrap()
+            .await?
             .json::<BasicResponse>()
-            .await
-            .unwrap();
+            .await?;
 
         if resp.code == 0 {
             Ok(())
@@ -96,83 +220,968 @@ impl Api {
         }
     }
 
+    // `group` is required by temp messages, where `target` is the member.
+    fn send_message_params(
+        &self,
+        chatroom_type: ChatroomType,
+        target: i64,
+        group: Option<i64>,
+        message_chain: MessageChain,
+        quote: Option<i64>,
+    ) -> Result<(&'static str, SendMessageParams)> {
+        let mut params = SendMessageParams {
+            session_key: self.session.clone(),
+            target: Some(target.to_string()),
+            qq: None,
+            group: None,
+            message_chain,
+            quote,
+        };
+
+        let path = match chatroom_type {
+            // a stranger is replied like a friend, the same as the uploads
+            ChatroomType::Friend | ChatroomType::Stranger => "/sendFriendMessage",
+            ChatroomType::Group => "/sendGroupMessage",
+            ChatroomType::Temp => match group {
+                Some(group) => {
+                    params.target = None;
+                    params.qq = Some(target);
+                    params.group = Some(group);
+                    "/sendTempMessage"
+                }
+                None => return Err(Error::new(
+                    "[Error] Sending temp message without group, use `send_temp_message` instead.",
+                )),
+            },
+        };
+
+        Ok((path, params))
+    }
+
     pub async fn send_message(
         &self,
         chatroom_type: ChatroomType,
         target: i64,
         message_chain: MessageChain,
-        quote: Option<i64>
-    ) -> Result<()> {
+        quote: Option<i64>,
+    ) -> Result<Option<i64>> {
+        let (path, params) =
+            self.send_message_params(chatroom_type, target, None, message_chain, quote)?;
+
+        let resp = self.post(path, &params).await?;
+
+        Ok(message_id(&resp))
+    }
+
+    pub async fn send_temp_message(
+        &self,
+        qq: i64,
+        group: i64,
+        message_chain: MessageChain,
+        quote: Option<i64>,
+    ) -> Result<Option<i64>> {
+        let (path, params) =
+            self.send_message_params(ChatroomType::Temp, qq, Some(group), message_chain, quote)?;
+
+        let resp = self.post(path, &params).await?;
+
+        Ok(message_id(&resp))
+    }
+
+    // Nudge `target` in the chat of `subject`, which is a friend, a group or a stranger.
+    pub async fn send_nudge(&self, target: i64, subject: i64, kind: SubjectKind) -> Result<()> {
         #[derive(Serialize)]
         #[serde(rename_all = "camelCase")]
         struct Params {
             session_key: String,
-            target: String,
-            message_chain: MessageChain,
-            quote: Option<i64>
+            target: i64,
+            subject: i64,
+            kind: SubjectKind,
         }
 
         let params = Params {
             session_key: self.session.clone(),
-            target: target.to_string(),
-            message_chain,
-            quote
+            target,
+            subject,
+            kind,
         };
 
-        let url = self.url(match chatroom_type {
-            ChatroomType::Friend => "/sendFriendMessage",
-            ChatroomType::Group => "/sendGroupMessage",
-        });
+        self.post("/sendNudge", &params).await?;
 
-        let resp = self
-            .client
-            .post(url)
-            .json(&params)
-            .send()
-            .await?
-            .json::<BasicResponse>()
-            .await?;
+        Ok(())
+    }
 
-        if resp.code == 0 {
-            Ok(())
-        } else {
-            Err(Error::new(&resp.msg))
+    // `target` is the friend or the group where the message is sent.
+    pub async fn recall(&self, target: i64, message_id: i64) -> Result<()> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params {
+            session_key: String,
+            target: i64,
+            message_id: i64,
         }
+
+        let params = Params {
+            session_key: self.session.clone(),
+            target,
+            message_id,
+        };
+
+        self.post("/recall", &params).await?;
+
+        Ok(())
     }
 
-    pub async fn fetch_messages(&self) -> Result<Vec<ReceivedMessage>> {
+    // Get a message cached by mirai, which is returned as a message event.
+    pub async fn message_from_id(&self, target: i64, message_id: i64) -> Result<Event> {
         #[derive(Serialize)]
         #[serde(rename_all = "camelCase")]
         struct Params {
             session_key: String,
-            count: i8,
+            target: i64,
+            message_id: i64,
         }
 
         let query = Params {
             session_key: self.session.clone(),
-            count: 10,
+            target,
+            message_id,
         };
 
-        #[derive(Deserialize)]
-        struct FetchResponse {
-            code: i32,
+        let resp = self.get("/messageFromId", &query).await?;
+
+        Ok(serde_json::from_value(resp["data"].clone())?)
+    }
+
+    // The command which mirai will execute when it's returned
+    // in the response body of the webhook.
+    pub fn send_message_command(
+        &self,
+        chatroom_type: ChatroomType,
+        target: i64,
+        group: Option<i64>,
+        message_chain: MessageChain,
+        quote: Option<i64>,
+    ) -> Result<Value> {
+        let (path, params) =
+            self.send_message_params(chatroom_type, target, group, message_chain, quote)?;
+
+        Ok(serde_json::json!({
+            "command": ws::command_name(path),
+            "content": serde_json::to_value(params)?,
+        }))
+    }
+
+    fn target_params(&self, target: i64) -> TargetParams {
+        TargetParams {
+            session_key: self.session.clone(),
+            target,
+        }
+    }
+
+    fn member_params(&self, group: i64, member: i64) -> MemberParams {
+        MemberParams {
+            session_key: self.session.clone(),
+            target: group,
+            member_id: member,
+        }
+    }
+
+    pub async fn mute(&self, group: i64, member: i64, duration: Duration) -> Result<()> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params {
+            #[serde(flatten)]
+            member: MemberParams,
+            time: u64,
+        }
+
+        if duration.as_secs() == 0 || duration > MAX_MUTE_DURATION {
+            return Err(Error::new(
+                "[Error] The mute duration should be between 1 second and 30 days.",
+            ));
+        }
+
+        let params = Params {
+            member: self.member_params(group, member),
+            time: duration.as_secs(),
+        };
+
+        self.post("/mute", &params).await?;
+
+        Ok(())
+    }
+
+    pub async fn unmute(&self, group: i64, member: i64) -> Result<()> {
+        self.post("/unmute", &self.member_params(group, member))
+            .await?;
+
+        Ok(())
+    }
+
+    // `msg` is the message shown to the kicked member.
+    pub async fn kick(&self, group: i64, member: i64, msg: &str) -> Result<()> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params {
+            #[serde(flatten)]
+            member: MemberParams,
             msg: String,
-            data: Vec<ReceivedMessage>,
         }
 
+        let params = Params {
+            member: self.member_params(group, member),
+            msg: msg.to_string(),
+        };
+
+        self.post("/kick", &params).await?;
+
+        Ok(())
+    }
+
+    pub async fn mute_all(&self, group: i64) -> Result<()> {
+        self.post("/muteAll", &self.target_params(group)).await?;
+
+        Ok(())
+    }
+
+    pub async fn unmute_all(&self, group: i64) -> Result<()> {
+        self.post("/unmuteAll", &self.target_params(group)).await?;
+
+        Ok(())
+    }
+
+    // Leave the group.
+    pub async fn quit(&self, group: i64) -> Result<()> {
+        self.post("/quit", &self.target_params(group)).await?;
+
+        Ok(())
+    }
+
+    // Assign or revoke the administrator, only the owner can do this.
+    pub async fn set_member_admin(&self, group: i64, member: i64, assign: bool) -> Result<()> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params {
+            #[serde(flatten)]
+            member: MemberParams,
+            assign: bool,
+        }
+
+        let params = Params {
+            member: self.member_params(group, member),
+            assign,
+        };
+
+        self.post("/memberAdmin", &params).await?;
+
+        Ok(())
+    }
+
+    pub async fn set_essence(&self, group: i64, message_id: i64) -> Result<()> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params {
+            #[serde(flatten)]
+            group: TargetParams,
+            message_id: i64,
+        }
+
+        let params = Params {
+            group: self.target_params(group),
+            message_id,
+        };
+
+        self.post("/setEssence", &params).await?;
+
+        Ok(())
+    }
+
+    pub async fn group_config(&self, group: i64) -> Result<GroupConfig> {
+        let resp = self.get("/groupConfig", &self.target_params(group)).await?;
+
+        Ok(serde_json::from_value(resp)?)
+    }
+
+    // Only the fields which are `Some` will be updated.
+    pub async fn set_group_config(&self, group: i64, config: GroupConfig) -> Result<()> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params {
+            #[serde(flatten)]
+            group: TargetParams,
+            config: GroupConfig,
+        }
+
+        let params = Params {
+            group: self.target_params(group),
+            config,
+        };
+
+        self.post("/groupConfig", &params).await?;
+
+        Ok(())
+    }
+
+    pub async fn member_info(&self, group: i64, member: i64) -> Result<MemberInfo> {
+        let resp = self
+            .get("/memberInfo", &self.member_params(group, member))
+            .await?;
+
+        Ok(MemberInfo {
+            name: resp["memberName"].as_str().map(|name| name.to_string()),
+            special_title: resp["specialTitle"].as_str().map(|title| title.to_string()),
+        })
+    }
+
+    // Only the fields which are `Some` will be updated.
+    pub async fn set_member_info(&self, group: i64, member: i64, info: MemberInfo) -> Result<()> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params {
+            #[serde(flatten)]
+            member: MemberParams,
+            info: MemberInfo,
+        }
+
+        let params = Params {
+            member: self.member_params(group, member),
+            info,
+        };
+
+        self.post("/memberInfo", &params).await?;
+
+        Ok(())
+    }
+
+    fn session_params(&self) -> SessionParams {
+        SessionParams {
+            session_key: self.session.clone(),
+        }
+    }
+
+    pub async fn friend_list(&self) -> Result<Vec<FriendSender>> {
+        let resp = self.get("/friendList", &self.session_params()).await?;
+
+        Ok(serde_json::from_value(resp["data"].clone())?)
+    }
+
+    pub async fn group_list(&self) -> Result<Vec<Group>> {
+        let resp = self.get("/groupList", &self.session_params()).await?;
+
+        Ok(serde_json::from_value(resp["data"].clone())?)
+    }
+
+    pub async fn member_list(&self, group: i64) -> Result<Vec<GroupSender>> {
+        let resp = self.get("/memberList", &self.target_params(group)).await?;
+
+        Ok(serde_json::from_value(resp["data"].clone())?)
+    }
+
+    // Unlike `member_list` which may be cached by mirai,
+    // the members are fetched from the server.
+    pub async fn latest_member_list(&self, group: i64) -> Result<Vec<GroupSender>> {
+        let resp = self
+            .get("/latestMemberList", &self.target_params(group))
+            .await?;
+
+        Ok(serde_json::from_value(resp["data"].clone())?)
+    }
+
+    pub async fn bot_profile(&self) -> Result<Profile> {
+        let resp = self.get("/botProfile", &self.session_params()).await?;
+
+        Ok(serde_json::from_value(resp)?)
+    }
+
+    pub async fn friend_profile(&self, friend: i64) -> Result<Profile> {
+        let resp = self
+            .get("/friendProfile", &self.target_params(friend))
+            .await?;
+
+        Ok(serde_json::from_value(resp)?)
+    }
+
+    pub async fn member_profile(&self, group: i64, member: i64) -> Result<Profile> {
+        let resp = self
+            .get("/memberProfile", &self.member_params(group, member))
+            .await?;
+
+        Ok(serde_json::from_value(resp)?)
+    }
+
+    pub async fn user_profile(&self, qq: i64) -> Result<Profile> {
+        let resp = self.get("/userProfile", &self.target_params(qq)).await?;
+
+        Ok(serde_json::from_value(resp)?)
+    }
+
+    // Respond to a request event, `message` is shown to the requester when rejecting.
+    pub async fn respond_request(
+        &self,
+        event: &Event,
+        operation: RequestOperation,
+        message: &str,
+    ) -> Result<()> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params {
+            session_key: String,
+            event_id: i64,
+            from_id: i64,
+            group_id: i64,
+            operate: i32,
+            message: String,
+        }
+
+        let (path, event_id, from_id, group_id, operate) = request_operate(event, operation)?;
+
+        let params = Params {
+            session_key: self.session.clone(),
+            event_id,
+            from_id,
+            group_id,
+            operate,
+            message: message.to_string(),
+        };
+
+        self.post(path, &params).await?;
+
+        Ok(())
+    }
+
+    // Files are always uploaded through http, as websocket doesn't support multipart.
+    pub(crate) async fn post_multipart(&self, path: &str, form: Form) -> Result<Value> {
+        let form = form.text("sessionKey", self.session.clone());
+
         let resp = self
             .client
-            .get(self.url("/fetchMessage"))
-            .query(&query)
+            .post(self.url(path))
+            .multipart(form)
             .send()
             .await?
-            .json::<FetchResponse>()
+            .json::<Value>()
             .await?;
 
-        if resp.code == 0 {
-            Ok(resp.data)
-        } else {
-            Err(Error::new(&resp.msg))
+        check_code(resp)
+    }
+
+    async fn upload(
+        &self,
+        path: &'static str,
+        chatroom_type: ChatroomType,
+        files: Vec<(&'static str, Vec<u8>)>,
+        id_field: &str,
+    ) -> Result<String> {
+        let upload_type = match chatroom_type {
+            ChatroomType::Friend | ChatroomType::Stranger => "friend",
+            ChatroomType::Group => "group",
+            ChatroomType::Temp => "temp",
+        };
+
+        let key = (
+            path,
+            upload_type,
+            files.iter().map(|(_, bytes)| content_hash(bytes)).collect(),
+        );
+
+        if let Some(id) = self.upload_cache.lock().unwrap().get(&key) {
+            return Ok(id);
+        }
+
+        let mut form = Form::new().text("type", upload_type);
+        for (name, bytes) in files {
+            form = form.part(name, Part::bytes(bytes).file_name(name));
+        }
+
+        let resp = self.post_multipart(path, form).await?;
+
+        let id = match resp[id_field].as_str() {
+            Some(id) => id.to_string(),
+            None => {
+                return Err(Error::new(&format!(
+                    "[Error] No `{}` in the response of `{}`.",
+                    id_field, path
+                )))
+            }
+        };
+
+        self.upload_cache.lock().unwrap().insert(key, id.clone());
+
+        Ok(id)
+    }
+
+    // `chatroom_type` is where the image will be sent.
+    pub async fn upload_image<S: Into<MediaSource>>(
+        &self,
+        chatroom_type: ChatroomType,
+        image: S,
+    ) -> Result<ImageId> {
+        let image = image.into().into_bytes().await?;
+        let id = self
+            .upload(
+                "/uploadImage",
+                chatroom_type,
+                vec![("img", image)],
+                "imageId",
+            )
+            .await?;
+
+        Ok(ImageId(id))
+    }
+
+    // The voice should be in the format of amr or silk.
+    pub async fn upload_voice<S: Into<MediaSource>>(
+        &self,
+        chatroom_type: ChatroomType,
+        voice: S,
+    ) -> Result<VoiceId> {
+        let voice = voice.into().into_bytes().await?;
+        let id = self
+            .upload(
+                "/uploadVoice",
+                chatroom_type,
+                vec![("voice", voice)],
+                "voiceId",
+            )
+            .await?;
+
+        Ok(VoiceId(id))
+    }
+
+    pub async fn upload_short_video<S: Into<MediaSource>, T: Into<MediaSource>>(
+        &self,
+        chatroom_type: ChatroomType,
+        video: S,
+        thumbnail: T,
+    ) -> Result<VideoId> {
+        let video = video.into().into_bytes().await?;
+        let thumbnail = thumbnail.into().into_bytes().await?;
+        let id = self
+            .upload(
+                "/uploadShortVideo",
+                chatroom_type,
+                vec![("video", video), ("thumbnail", thumbnail)],
+                "videoId",
+            )
+            .await?;
+
+        Ok(VideoId(id))
+    }
+
+    // List a page of the announcements, starting from `offset`.
+    pub async fn announcements(
+        &self,
+        group: i64,
+        offset: i64,
+        size: i64,
+    ) -> Result<Vec<Announcement>> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params {
+            session_key: String,
+            id: i64,
+            offset: i64,
+            size: i64,
+        }
+
+        let query = Params {
+            session_key: self.session.clone(),
+            id: group,
+            offset,
+            size,
+        };
+
+        let resp = self.get("/anno/list", &query).await?;
+
+        Ok(serde_json::from_value(resp["data"].clone())?)
+    }
+
+    pub async fn publish_announcement(
+        &self,
+        group: i64,
+        content: &str,
+        options: PublishOptions,
+    ) -> Result<Announcement> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params {
+            #[serde(flatten)]
+            group: TargetParams,
+            content: String,
+            #[serde(flatten)]
+            options: PublishOptions,
+        }
+
+        let params = Params {
+            group: self.target_params(group),
+            content: content.to_string(),
+            options,
+        };
+
+        let resp = self.post("/anno/publish", &params).await?;
+
+        Ok(serde_json::from_value(resp["data"].clone())?)
+    }
+
+    pub async fn delete_announcement(&self, group: i64, fid: &str) -> Result<()> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params {
+            session_key: String,
+            id: i64,
+            fid: String,
+        }
+
+        let params = Params {
+            session_key: self.session.clone(),
+            id: group,
+            fid: fid.to_string(),
+        };
+
+        self.post("/anno/delete", &params).await?;
+
+        Ok(())
+    }
+
+    pub fn group_files(&self, group: i64) -> GroupFiles {
+        GroupFiles::new(self.clone(), group)
+    }
+
+    pub async fn fetch_messages(&self, count: i32) -> Result<Vec<Event>> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params {
+            session_key: String,
+            count: i32,
+        }
+
+        let query = Params {
+            session_key: self.session.clone(),
+            count,
+        };
+
+        let resp = self.get("/fetchMessage", &query).await?;
+
+        Ok(serde_json::from_value(resp["data"].clone())?)
+    }
+
+    // The number of messages still queued in mirai.
+    pub async fn count_messages(&self) -> Result<i64> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params {
+            session_key: String,
+        }
+
+        let query = Params {
+            session_key: self.session.clone(),
+        };
+
+        let resp = self.get("/countMessage", &query).await?;
+
+        Ok(resp["data"].as_i64().unwrap_or(0))
+    }
+}
+
+fn check_code(resp: Value) -> Result<Value> {
+    let code = resp["code"].as_i64().unwrap_or(0);
+
+    if code == 0 {
+        Ok(resp)
+    } else {
+        Err(Error::new(&error_message(
+            code,
+            resp["msg"].as_str().unwrap_or(""),
+        )))
+    }
+}
+
+// Explain the status codes of mirai-api-http,
+// the problems of permission are the most common ones for group apis.
+fn error_message(code: i64, msg: &str) -> String {
+    let reason = match code {
+        1 => "The verify key is wrong.",
+        2 => "The bot doesn't exist.",
+        3 => "The session is invalid or expired.",
+        4 => "The session is not verified.",
+        5 => "The target doesn't exist.",
+        6 => "The file doesn't exist.",
+        10 => "The bot has no permission, e.g. it's not an administrator of the group or the target is.",
+        20 => "The bot is muted in the group.",
+        30 => "The message is too long.",
+        400 => "The request is invalid.",
+        _ => "",
+    };
+
+    if reason.is_empty() {
+        format!("[Error] mirai returned {}: {}", code, msg)
+    } else {
+        format!("[Error] {} (mirai returned {}: {})", reason, code, msg)
+    }
+}
+
+type RequestOperate = (&'static str, i64, i64, i64, i32);
+
+// Return the path, `eventId`, `fromId`, `groupId` and `operate` of the response.
+fn request_operate(event: &Event, operation: RequestOperation) -> Result<RequestOperate> {
+    use RequestOperation::*;
+
+    let operate = match event {
+        Event::NewFriendRequestEvent {
+            event_id,
+            from_id,
+            group_id,
+            ..
+        } => {
+            let operate = match operation {
+                Accept => 0,
+                Reject { blacklist: false } => 1,
+                Reject { blacklist: true } => 2,
+                Ignore { .. } => {
+                    return Err(Error::new("[Error] The friend request can't be ignored."))
+                }
+            };
+            (
+                "/resp/newFriendRequestEvent",
+                *event_id,
+                *from_id,
+                *group_id,
+                operate,
+            )
+        }
+
+        Event::MemberJoinRequestEvent {
+            event_id,
+            from_id,
+            group_id,
+            ..
+        } => {
+            let operate = match operation {
+                Accept => 0,
+                Reject { blacklist: false } => 1,
+                Ignore { blacklist: false } => 2,
+                Reject { blacklist: true } => 3,
+                Ignore { blacklist: true } => 4,
+            };
+            (
+                "/resp/memberJoinRequestEvent",
+                *event_id,
+                *from_id,
+                *group_id,
+                operate,
+            )
+        }
+
+        Event::BotInvitedJoinGroupRequestEvent {
+            event_id,
+            from_id,
+            group_id,
+            ..
+        } => {
+            let operate = match operation {
+                Accept => 0,
+                Reject { blacklist: false } => 1,
+                Reject { blacklist: true } => {
+                    return Err(Error::new(
+                        "[Error] The group invitation can't be rejected with blacklist.",
+                    ))
+                }
+                Ignore { .. } => {
+                    return Err(Error::new("[Error] The group invitation can't be ignored."))
+                }
+            };
+            (
+                "/resp/botInvitedJoinGroupRequestEvent",
+                *event_id,
+                *from_id,
+                *group_id,
+                operate,
+            )
+        }
+
+        _ => {
+            return Err(Error::new(
+                "[Error] Responding to an event which is not a request.",
+            ))
+        }
+    };
+
+    Ok(operate)
+}
+
+// The id of the sent message, `None` if mirai omits it or fails to get it (`-1`).
+fn message_id(resp: &Value) -> Option<i64> {
+    resp["messageId"].as_i64().filter(|id| *id != -1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{error_message, message_id, request_operate, Api};
+    use crate::event::{Event, RequestOperation};
+    use crate::message::media::ImageId;
+    use crate::message::{create_plain_message_chain, ChatroomType};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn check_send_message_params() {
+        let api = Api::new(20211113, "http://mirai.host", "session");
+        let message_chain = create_plain_message_chain("hi".to_string());
+
+        let (path, params) = api
+            .send_message_params(
+                ChatroomType::Temp,
+                20211114,
+                Some(20211115),
+                message_chain.clone(),
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(path, "/sendTempMessage");
+        assert_eq!(
+            serde_json::to_value(params).unwrap(),
+            json!({
+                "sessionKey": "session",
+                "qq": 20211114,
+                "group": 20211115,
+                "messageChain": [{"type": "Plain", "text": "hi"}],
+                "quote": null
+            })
+        );
+
+        let (path, _) = api
+            .send_message_params(
+                ChatroomType::Friend,
+                20211114,
+                None,
+                message_chain.clone(),
+                None,
+            )
+            .unwrap();
+        assert_eq!(path, "/sendFriendMessage");
+
+        assert!(api
+            .send_message_params(
+                ChatroomType::Temp,
+                20211114,
+                None,
+                message_chain.clone(),
+                None
+            )
+            .is_err());
+
+        let (path, params) = api
+            .send_message_params(ChatroomType::Stranger, 20211114, None, message_chain, None)
+            .unwrap();
+        assert_eq!(path, "/sendFriendMessage");
+        assert_eq!(params.target, Some("20211114".to_string()));
+    }
+
+    #[test]
+    fn check_error_message() {
+        assert_eq!(
+            error_message(20, "bot is muted"),
+            "[Error] The bot is muted in the group. (mirai returned 20: bot is muted)"
+        );
+        assert_eq!(error_message(42, "oops"), "[Error] mirai returned 42: oops");
+    }
+
+    #[test]
+    fn check_request_operate() {
+        let event = Event::MemberJoinRequestEvent {
+            event_id: 1,
+            from_id: 20211113,
+            group_id: 20211114,
+            group_name: "木木".to_string(),
+            nick: "Thungghuan".to_string(),
+            message: "hi".to_string(),
+            invitor_id: None,
+        };
+
+        assert_eq!(
+            request_operate(&event, RequestOperation::Ignore { blacklist: true }).unwrap(),
+            ("/resp/memberJoinRequestEvent", 1, 20211113, 20211114, 4)
+        );
+
+        let event = Event::NewFriendRequestEvent {
+            event_id: 1,
+            from_id: 20211113,
+            group_id: 0,
+            nick: "Thungghuan".to_string(),
+            message: "hi".to_string(),
+        };
+
+        assert_eq!(
+            request_operate(&event, RequestOperation::Reject { blacklist: true }).unwrap(),
+            ("/resp/newFriendRequestEvent", 1, 20211113, 0, 2)
+        );
+        assert!(request_operate(&event, RequestOperation::Ignore { blacklist: false }).is_err());
+
+        assert!(request_operate(
+            &Event::BotOnlineEvent { qq: 20211113 },
+            RequestOperation::Accept
+        )
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn upload_image_once() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let uploads = Arc::new(AtomicUsize::new(0));
+
+        let counter = uploads.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+
+                // read until the end of the multipart body
+                let mut req = vec![];
+                let mut chunk = [0; 4096];
+                while !req.ends_with(b"--\r\n") {
+                    let n = stream.read(&mut chunk).await.unwrap();
+                    req.extend_from_slice(&chunk[..n]);
+                }
+
+                let body = r#"{"imageId":"{01E9451B}.jpg","url":""}"#;
+                let resp = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(resp.as_bytes()).await.unwrap();
+            }
+        });
+
+        let api = Api::new(20211113, &format!("http://{}", address), "session");
+
+        for _ in 0..2 {
+            let id = api
+                .upload_image(ChatroomType::Group, &b"meme"[..])
+                .await
+                .unwrap();
+            assert_eq!(id, ImageId("{01E9451B}.jpg".to_string()));
         }
+
+        assert_eq!(uploads.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn check_message_id() {
+        assert_eq!(
+            message_id(&json!({"code": 0, "msg": "success", "messageId": 7})),
+            Some(7)
+        );
+        assert_eq!(message_id(&json!({"code": 0, "msg": "success"})), None);
+        assert_eq!(
+            message_id(&json!({"code": 0, "msg": "success", "messageId": -1})),
+            None
+        );
     }
 }
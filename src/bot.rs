@@ -1,46 +1,119 @@
+use futures_util::FutureExt;
+use serde_json::Value;
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
 use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::task::JoinHandle;
 use tokio::time::sleep;
 
+use crate::adapter::{Adapter, WebhookRequest, WebhookServer, WsClient};
+use crate::announcement::{Announcement, PublishOptions};
 use crate::api::Api;
-use crate::context::Context;
-use crate::event_listener::{EventListener, EventType};
-use crate::message::{ChatroomType, MessageChain, ReceivedMessage};
+use crate::command::{help_text, Command, CommandConfig, CommandInfo};
+use crate::context::{Context, WebhookReply};
+use crate::error::Error;
+use crate::event::{Event, SubjectKind};
+use crate::event_listener::{EventListener, EventType, ListenerInfo};
+use crate::files::GroupFiles;
+use crate::message::media::{ImageId, MediaSource, VideoId, VoiceId};
+use crate::message::sender::{FriendSender, Group, GroupConfig, GroupSender, MemberInfo, Profile};
+use crate::message::{ChatroomType, MessageChain};
+use crate::middleware::{BoxFuture, Middleware, Next};
+use crate::scheduler::Scheduler;
 use crate::utils::BotConfig;
 use crate::Result;
 
+// The clones share the listeners, so that the events can be handled in spawned tasks.
+#[derive(Clone)]
 pub struct Bot {
     qq: i64,
     master_qq: i64,
     session: String,
     api: Api,
+    adapter: Adapter,
 
+    // polling settings, only used by the http adapter
+    fetch_count: i32,
+    poll_interval: Duration,
+    max_poll_interval: Duration,
+    drain: bool,
+
+    // the max number of events handled at the same time
+    concurrency: usize,
+    // whether the events in the same chat are handled one by one
+    ordered: bool,
+
+    dispatcher: Arc<Dispatcher>,
+}
+
+// Everything registered to the bot, which is copied on write
+// if it's changed after the bot is cloned.
+#[derive(Clone, Default)]
+struct Dispatcher {
     event_listeners: Vec<EventListener>,
-    commands: Vec<String>,
+    middlewares: Vec<Arc<dyn Middleware>>,
+    commands: Vec<CommandInfo>,
+    command_config: CommandConfig,
+
+    // the errors are logged if no hook is set
+    error_hook: Option<Arc<ErrorHook>>,
+    error_reply: Option<String>,
+
+    // added by `bot.manage(state)`, one for each type
+    states: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
 }
 
+type ErrorHook = dyn Fn(Context, Error, ListenerInfo) -> BoxFuture<'static> + Send + Sync;
+
+impl Dispatcher {
+    // Keep the listeners sorted by priority,
+    // and the ones with the same priority are in the order they are added.
+    fn add_listener(&mut self, mut listener: EventListener) {
+        listener.set_index(self.event_listeners.len());
+
+        let position = self
+            .event_listeners
+            .iter()
+            .position(|other| other.priority() < listener.priority())
+            .unwrap_or(self.event_listeners.len());
+
+        self.event_listeners.insert(position, listener);
+    }
+}
+
+const DEFAULT_FETCH_COUNT: i32 = 10;
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_POLL_INTERVAL: Duration = Duration::from_secs(8);
+const DEFAULT_CONCURRENCY: usize = 16;
+
+// The first retry of a closed ws connection, doubled after each failure.
+const WS_RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_WS_RECONNECT_INTERVAL: Duration = Duration::from_secs(60);
+
+type WsConnection = (WsClient, UnboundedReceiver<Value>);
+
 impl Bot {
     pub fn new(config: BotConfig, session: &str, base_url: &str) -> Self {
         Bot {
-            qq: config.qq.clone(),
-            master_qq: config.master_qq.clone(),
+            qq: config.qq,
+            master_qq: config.master_qq,
             session: session.to_string(),
             api: Api::new(config.qq, base_url, session),
+            adapter: config.adapter,
 
-            event_listeners: vec![],
-            commands: vec![],
-        }
-    }
+            fetch_count: DEFAULT_FETCH_COUNT,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            max_poll_interval: DEFAULT_MAX_POLL_INTERVAL,
+            drain: true,
 
-    pub fn clone(&self) -> Self {
-        Bot {
-            qq: self.qq.clone(),
-            master_qq: self.master_qq.clone(),
-            session: self.session.clone(),
-            api: self.api.clone(),
+            concurrency: DEFAULT_CONCURRENCY,
+            ordered: false,
 
-            event_listeners: vec![],
-            commands: vec![],
+            dispatcher: Default::default(),
         }
     }
 
@@ -56,6 +129,51 @@ impl Bot {
         self.session.clone()
     }
 
+    // The max number of messages fetched at one time.
+    pub fn set_fetch_count(&mut self, count: i32) {
+        self.fetch_count = count.max(1);
+    }
+
+    // The interval between polls, which doubles while the queue is idle,
+    // up to `max_interval`.
+    pub fn set_poll_interval(&mut self, interval: Duration, max_interval: Duration) {
+        self.poll_interval = interval;
+        self.max_poll_interval = max_interval.max(interval);
+    }
+
+    // Keep fetching without sleeping while messages are still queued in mirai.
+    pub fn set_drain(&mut self, drain: bool) {
+        self.drain = drain;
+    }
+
+    // The prefixes of commands, and whether an at is required in groups.
+    pub fn set_command_config(&mut self, config: CommandConfig) {
+        self.dispatcher_mut().command_config = config;
+    }
+
+    pub fn command_config(&self) -> &CommandConfig {
+        &self.dispatcher.command_config
+    }
+
+    // The max number of events handled at the same time, each of them in a spawned task.
+    pub fn set_concurrency(&mut self, limit: usize) {
+        self.concurrency = limit.max(1);
+    }
+
+    // Handle the events in the same chat one by one, in the order they are received.
+    // The events in different chats are still handled at the same time.
+    pub fn set_ordered(&mut self, ordered: bool) {
+        self.ordered = ordered;
+    }
+
+    fn dispatcher_mut(&mut self) -> &mut Dispatcher {
+        Arc::make_mut(&mut self.dispatcher)
+    }
+
+    pub(crate) fn api(&self) -> &Api {
+        &self.api
+    }
+
     pub async fn start_with_callback<'a, F, T>(&'a self, cb: F)
     where
         F: Fn(&'a Bot) -> T,
@@ -73,6 +191,14 @@ impl Bot {
             }
         };
 
+        // Connect the adapter before the callback,
+        // so that the callback can send messages through it.
+        let events = if will_bot_start {
+            self.connect().await
+        } else {
+            None
+        };
+
         if will_bot_start {
             will_bot_start = match cb(self).await {
                 Ok(_) => true,
@@ -89,7 +215,7 @@ impl Bot {
         if will_bot_start {
             tokio::select! {
                 _ = async {
-                    self.listen().await;
+                    self.listen(events).await;
                 } => {}
                 _ = tokio::signal::ctrl_c() => {
                     println!("\nCtrl+C received.\nReleasing session...");
@@ -112,11 +238,146 @@ impl Bot {
         self.start_with_callback(basic_start_callback).await;
     }
 
-    async fn listen(&self) {
+    async fn connect(&self) -> Option<UnboundedReceiver<Value>> {
+        match &self.adapter {
+            Adapter::Http | Adapter::Webhook { .. } => None,
+            Adapter::Ws(url) => match WsClient::connect(url, &self.session).await {
+                Ok((ws_client, events)) => {
+                    self.api.set_ws_client(Some(ws_client));
+                    Some(events)
+                }
+                Err(e) => {
+                    eprintln!(
+                        "[Error] Connecting to the ws adapter.\n{}\nPolling through http until it's reconnected.",
+                        e
+                    );
+                    None
+                }
+            },
+        }
+    }
+
+    async fn listen(&self, events: Option<UnboundedReceiver<Value>>) {
         println!("The bot is running...");
 
+        let mut scheduler = Scheduler::new(self.concurrency, self.ordered);
+
+        if let Adapter::Webhook { address, reply } = &self.adapter {
+            match WebhookServer::bind(address).await {
+                Ok(server) => self.serve_webhook(server, *reply, &mut scheduler).await,
+                Err(e) => eprintln!(
+                    "[Error] Starting the webhook server at {}.\n{}\nFalling back to http polling.",
+                    address, e
+                ),
+            }
+        }
+
+        let url = match &self.adapter {
+            Adapter::Ws(url) => url.clone(),
+            _ => {
+                self.poll(&mut scheduler, None).await;
+                return;
+            }
+        };
+
+        let mut events = events;
         loop {
-            let messages = match self.api.fetch_messages().await {
+            if let Some(events) = events.take() {
+                self.listen_ws(events, &mut scheduler).await;
+
+                // The connection is closed, messages can still be polled through http.
+                eprintln!("[Error] The ws connection is closed.\nPolling through http until it's reconnected.");
+                self.api.set_ws_client(None);
+            }
+
+            let bot = self.clone();
+            let url = url.clone();
+            let reconnected = tokio::spawn(async move { bot.reconnect(&url).await });
+
+            if let Some((ws_client, ws_events)) = self.poll(&mut scheduler, Some(reconnected)).await
+            {
+                println!("The ws adapter is reconnected.");
+                self.api.set_ws_client(Some(ws_client));
+                events = Some(ws_events);
+            }
+        }
+    }
+
+    // Retry with backoff until it's connected.
+    async fn reconnect(&self, url: &str) -> WsConnection {
+        let mut interval = WS_RECONNECT_INTERVAL;
+
+        loop {
+            sleep(interval).await;
+
+            match WsClient::connect(url, &self.session).await {
+                Ok(connection) => return connection,
+                Err(e) => eprintln!("[Error] Reconnecting to the ws adapter.\n{}", e),
+            }
+
+            interval = (interval * 2).min(MAX_WS_RECONNECT_INTERVAL);
+        }
+    }
+
+    async fn listen_ws(&self, mut events: UnboundedReceiver<Value>, scheduler: &mut Scheduler) {
+        while let Some(event) = events.recv().await {
+            let event = match serde_json::from_value::<Event>(event) {
+                Ok(message) => message,
+                Err(e) => {
+                    eprintln!("[Error] Parsing pushed message.\n{}", e);
+                    continue;
+                }
+            };
+
+            scheduler.spawn(self, event).await;
+        }
+    }
+
+    // Each request is handled like the other events, and responded after the handlers.
+    async fn serve_webhook(&self, server: WebhookServer, reply: bool, scheduler: &mut Scheduler) {
+        let mut requests = server.incoming();
+
+        while let Some(req) = requests.recv().await {
+            let ctx = serde_json::from_value::<Event>(req.body().clone())
+                .map_err(Error::from)
+                .and_then(|event| Context::new(self.clone(), event));
+
+            let mut ctx = match ctx {
+                Ok(ctx) => ctx,
+                Err(e) => {
+                    eprintln!("[Error] Handling posted message.\n{}", e);
+                    tokio::spawn(respond_webhook(req, None));
+                    continue;
+                }
+            };
+
+            let webhook_reply: Option<WebhookReply> = if reply {
+                Some(Default::default())
+            } else {
+                None
+            };
+            if let Some(webhook_reply) = &webhook_reply {
+                ctx.set_webhook_reply(webhook_reply.clone());
+            }
+
+            scheduler
+                .spawn_context(self, ctx, respond_webhook(req, webhook_reply))
+                .await;
+        }
+    }
+
+    // Poll until the ws adapter is reconnected, or forever if there is no ws adapter.
+    // The reconnection is only checked while sleeping, so that no fetched message is lost.
+    async fn poll(
+        &self,
+        scheduler: &mut Scheduler,
+        mut reconnected: Option<JoinHandle<WsConnection>>,
+    ) -> Option<WsConnection> {
+        // the number of idle polls in a row
+        let mut idle_polls = 0;
+
+        loop {
+            let messages = match self.api.fetch_messages(self.fetch_count).await {
                 Ok(messages) => messages,
                 Err(e) => {
                     eprintln!("[Error] Fetching message.\n{}", e);
@@ -124,83 +385,357 @@ impl Bot {
                 }
             };
 
+            let idle = messages.is_empty();
+
             for message in messages {
-                if let Err(e) = self.handler(message).await {
-                    eprintln!("[Error] Handling message.\n{}", e);
+                scheduler.spawn(self, message).await;
+            }
+
+            if self.drain && !idle {
+                match self.api.count_messages().await {
+                    // drain the queue without sleeping
+                    Ok(remaining) if remaining > 0 => {
+                        idle_polls = 0;
+                        continue;
+                    }
+                    Ok(_) => {}
+                    Err(e) => eprintln!("[Error] Counting message.\n{}", e),
                 }
             }
 
-            // fetch messages for every second.
-            sleep(Duration::from_secs(1)).await;
+            idle_polls = if idle { idle_polls + 1 } else { 0 };
+            let wait = sleep(poll_interval(
+                self.poll_interval,
+                self.max_poll_interval,
+                idle_polls,
+            ));
+
+            match &mut reconnected {
+                Some(reconnected) => tokio::select! {
+                    _ = wait => {}
+                    // the reconnection is tried again if it panics
+                    connection = reconnected => return connection.ok(),
+                },
+                None => wait.await,
+            }
         }
     }
 
     fn will_handle(&self, ctx: &Context, listener: &EventListener) -> bool {
         // Distinguish between common message and command
         match listener.event_type() {
-            EventType::FriendMessage => {
-                !ctx.is_command() && ctx.chatroom_type() == ChatroomType::Friend
-            }
-            EventType::GroupMessage => {
-                !ctx.is_command() && ctx.chatroom_type() == ChatroomType::Group
-            }
-            EventType::Message => !ctx.is_command(),
+            EventType::Message => !ctx.is_command() && ctx.is_message(),
             EventType::Command => {
                 ctx.is_command()
-                    && ctx.command_name() != ""
-                    // use `bot.on("command", handler)` to handle all command
-                    && ((listener.command_name() == None
-                        && !self.commands.contains(&ctx.command_name().to_string()))
+                    && !ctx.command_name().is_empty()
+                    && match listener.command() {
                         // use `bot.command("command_name", handler)` to handle specific command
-                        || ctx.command_name() == listener.command_name().unwrap_or("".to_string()))
+                        Some(command) => self
+                            .dispatcher
+                            .command_config
+                            .matches(command, ctx.command_name()),
+                        // use `bot.on("command", handler)` to handle all command
+                        None => !self.is_registered(ctx.command_name()),
+                    }
             }
-            _ => false,
+            EventType::Invalid(_) => false,
+            event_type => !ctx.is_command() && event_type == EventType::from(ctx.event()),
         }
     }
 
-    async fn handler(&self, message: ReceivedMessage) -> Result<()> {
-        // Fix the f**king lifetime error by just cloning it
-        // instead of borrowing it.
-        let ctx = match message {
-            ReceivedMessage::FriendMessage {
-                sender,
-                message_chain,
-            } => Context::new(self.clone(), sender, message_chain)?,
+    // The errors of the listeners are handled by themselves,
+    // so the errors here come from the global middleware.
+    pub(crate) async fn dispatch_context(&self, ctx: Context) {
+        // the listeners are matched after the middleware, which may change the context
+        let endpoint = |ctx| Box::pin(self.handle_context(ctx)) as BoxFuture;
+        let next = Next::new(&self.dispatcher.middlewares, &endpoint);
 
-            ReceivedMessage::GroupMessage {
-                sender,
-                message_chain,
-            } => Context::new(self.clone(), sender, message_chain)?,
-        };
+        if let Err(e) = catch_panic(next.run(ctx.clone())).await {
+            let listener = ListenerInfo::middleware(&ctx);
+            self.handle_error(ctx, e, listener).await;
+        }
+    }
 
-        for listener in &self.event_listeners {
-            let will_handle = self.will_handle(&ctx, listener);
+    async fn handle_context(&self, ctx: Context) -> Result<()> {
+        // `/help` is answered unless it's registered by the user
+        let mut help = ctx.is_command()
+            && self
+                .dispatcher
+                .command_config
+                .name_eq(ctx.command_name(), "help")
+            && !self.is_registered("help");
 
-            if will_handle {
-                listener.handle(ctx.clone()).await.unwrap()
+        for listener in &self.dispatcher.event_listeners {
+            // `/help` is like a listener with priority 0 added before the others
+            if help && listener.priority() <= 0 {
+                help = false;
+                self.answer_help(&ctx).await;
+            }
+
+            // stopped by a previous listener or a middleware
+            if ctx.is_propagation_stopped() {
+                break;
+            }
+
+            if !self.will_handle(&ctx, listener) {
+                continue;
+            }
+
+            // the following listeners are still called if one fails
+            if let Err(e) = catch_panic(listener.handle(ctx.clone())).await {
+                self.handle_error(ctx.clone(), e, listener.info()).await;
             }
         }
 
+        if help {
+            self.answer_help(&ctx).await;
+        }
+
         Ok(())
     }
 
+    // The listeners for all the commands are not called after `/help`,
+    // as they are usually for the unknown commands.
+    async fn answer_help(&self, ctx: &Context) {
+        if ctx.is_propagation_stopped() {
+            return;
+        }
+
+        if let Err(e) = catch_panic(self.reply_help(ctx)).await {
+            let listener = ListenerInfo {
+                index: 0,
+                priority: 0,
+                event_type: EventType::Command.to_string(),
+                command: Some("help".to_string()),
+                middleware: false,
+            };
+            self.handle_error(ctx.clone(), e, listener).await;
+        }
+
+        ctx.stop_propagation();
+    }
+
+    async fn reply_help(&self, ctx: &Context) -> Result<()> {
+        let args = ctx.args()?;
+        let names = args.get::<Vec<String>>(0)?;
+        let text = help_text(
+            &self.dispatcher.commands,
+            &self.dispatcher.command_config,
+            &names,
+        );
+
+        ctx.reply(MessageChain::from(text)).await?;
+        Ok(())
+    }
+
+    async fn handle_error(&self, ctx: Context, e: Error, listener: ListenerInfo) {
+        let name = listener.to_string();
+
+        let result = match &self.dispatcher.error_hook {
+            Some(hook) => hook(ctx, e, listener).await,
+            None => {
+                eprintln!("[Error] Handling {}.\n{}", name, e);
+
+                match &self.dispatcher.error_reply {
+                    Some(reply) => ctx
+                        .reply(MessageChain::from(reply.as_str()))
+                        .await
+                        .map(|_| ()),
+                    None => Ok(()),
+                }
+            }
+        };
+
+        if let Err(e) = result {
+            eprintln!("[Error] Handling the error of {}.\n{}", name, e);
+        }
+    }
+
     pub async fn send_message(
         &self,
         chatroom_type: ChatroomType,
         target: i64,
         message_chain: MessageChain,
         quote: Option<i64>,
-    ) -> Result<()> {
+    ) -> Result<Option<i64>> {
         self.api
             .send_message(chatroom_type, target, message_chain, quote)
-            .await?;
-        Ok(())
+            .await
+    }
+
+    pub async fn send_temp_message(
+        &self,
+        qq: i64,
+        group: i64,
+        message_chain: MessageChain,
+        quote: Option<i64>,
+    ) -> Result<Option<i64>> {
+        self.api
+            .send_temp_message(qq, group, message_chain, quote)
+            .await
+    }
+
+    pub async fn send_nudge(&self, target: i64, subject: i64, kind: SubjectKind) -> Result<()> {
+        self.api.send_nudge(target, subject, kind).await
+    }
+
+    pub async fn recall(&self, target: i64, message_id: i64) -> Result<()> {
+        self.api.recall(target, message_id).await
+    }
+
+    pub async fn mute(&self, group: i64, member: i64, duration: Duration) -> Result<()> {
+        self.api.mute(group, member, duration).await
+    }
+
+    pub async fn unmute(&self, group: i64, member: i64) -> Result<()> {
+        self.api.unmute(group, member).await
+    }
+
+    pub async fn kick(&self, group: i64, member: i64, msg: &str) -> Result<()> {
+        self.api.kick(group, member, msg).await
+    }
+
+    pub async fn mute_all(&self, group: i64) -> Result<()> {
+        self.api.mute_all(group).await
+    }
+
+    pub async fn unmute_all(&self, group: i64) -> Result<()> {
+        self.api.unmute_all(group).await
+    }
+
+    pub async fn quit(&self, group: i64) -> Result<()> {
+        self.api.quit(group).await
+    }
+
+    pub async fn set_member_admin(&self, group: i64, member: i64, assign: bool) -> Result<()> {
+        self.api.set_member_admin(group, member, assign).await
+    }
+
+    pub async fn set_essence(&self, group: i64, message_id: i64) -> Result<()> {
+        self.api.set_essence(group, message_id).await
+    }
+
+    pub async fn group_config(&self, group: i64) -> Result<GroupConfig> {
+        self.api.group_config(group).await
+    }
+
+    pub async fn set_group_config(&self, group: i64, config: GroupConfig) -> Result<()> {
+        self.api.set_group_config(group, config).await
+    }
+
+    pub async fn member_info(&self, group: i64, member: i64) -> Result<MemberInfo> {
+        self.api.member_info(group, member).await
+    }
+
+    pub async fn set_member_info(&self, group: i64, member: i64, info: MemberInfo) -> Result<()> {
+        self.api.set_member_info(group, member, info).await
+    }
+
+    pub async fn friend_list(&self) -> Result<Vec<FriendSender>> {
+        self.api.friend_list().await
+    }
+
+    pub async fn group_list(&self) -> Result<Vec<Group>> {
+        self.api.group_list().await
+    }
+
+    pub async fn member_list(&self, group: i64) -> Result<Vec<GroupSender>> {
+        self.api.member_list(group).await
+    }
+
+    pub async fn latest_member_list(&self, group: i64) -> Result<Vec<GroupSender>> {
+        self.api.latest_member_list(group).await
+    }
+
+    pub async fn bot_profile(&self) -> Result<Profile> {
+        self.api.bot_profile().await
+    }
+
+    pub async fn friend_profile(&self, friend: i64) -> Result<Profile> {
+        self.api.friend_profile(friend).await
+    }
+
+    pub async fn member_profile(&self, group: i64, member: i64) -> Result<Profile> {
+        self.api.member_profile(group, member).await
+    }
+
+    pub async fn user_profile(&self, qq: i64) -> Result<Profile> {
+        self.api.user_profile(qq).await
+    }
+
+    pub async fn upload_image<S: Into<MediaSource>>(
+        &self,
+        chatroom_type: ChatroomType,
+        image: S,
+    ) -> Result<ImageId> {
+        self.api.upload_image(chatroom_type, image).await
+    }
+
+    pub async fn upload_voice<S: Into<MediaSource>>(
+        &self,
+        chatroom_type: ChatroomType,
+        voice: S,
+    ) -> Result<VoiceId> {
+        self.api.upload_voice(chatroom_type, voice).await
+    }
+
+    pub async fn upload_short_video<S: Into<MediaSource>, T: Into<MediaSource>>(
+        &self,
+        chatroom_type: ChatroomType,
+        video: S,
+        thumbnail: T,
+    ) -> Result<VideoId> {
+        self.api
+            .upload_short_video(chatroom_type, video, thumbnail)
+            .await
     }
 
-    pub fn on<F, Fut>(&mut self, event_type: &str, handler: &'static F)
+    pub async fn announcements(
+        &self,
+        group: i64,
+        offset: i64,
+        size: i64,
+    ) -> Result<Vec<Announcement>> {
+        self.api.announcements(group, offset, size).await
+    }
+
+    pub async fn publish_announcement(
+        &self,
+        group: i64,
+        content: &str,
+        options: PublishOptions,
+    ) -> Result<Announcement> {
+        self.api.publish_announcement(group, content, options).await
+    }
+
+    pub async fn delete_announcement(&self, group: i64, fid: &str) -> Result<()> {
+        self.api.delete_announcement(group, fid).await
+    }
+
+    pub fn group_files(&self, group: i64) -> GroupFiles {
+        self.api.group_files(group)
+    }
+
+    pub async fn message_from_id(&self, target: i64, message_id: i64) -> Result<Event> {
+        self.api.message_from_id(target, message_id).await
+    }
+
+    // The handler can be a closure with its own state, or a `&'static` reference.
+    pub fn on<F, Fut>(&mut self, event_type: &str, handler: F)
     where
-        F: Fn(Context) -> Fut,
-        Fut: Future<Output = Result<()>> + 'static,
+        F: Fn(Context) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.on_with_priority(event_type, 0, handler);
+    }
+
+    // The listeners with higher priority are called first, e.g. a spam filter
+    // which calls `ctx.stop_propagation()`, and a negative priority is for fallbacks.
+    // The ones with the same priority are called in the order they are added.
+    pub fn on_with_priority<F, Fut>(&mut self, event_type: &str, priority: i32, handler: F)
+    where
+        F: Fn(Context) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
     {
         let event_type = EventType::from(event_type);
 
@@ -209,32 +744,801 @@ impl Bot {
             return;
         }
 
-        self.event_listeners
-            .push(EventListener::new(event_type, handler, None));
+        self.dispatcher_mut()
+            .add_listener(EventListener::new(event_type, handler, None).with_priority(priority));
     }
 
-    pub fn command<F, Fut>(&mut self, command_name: &str, handler: &'static F)
+    pub fn command<F, Fut>(&mut self, command_name: &str, handler: F)
     where
-        F: Fn(Context) -> Fut,
-        Fut: Future<Output = Result<()>> + 'static,
+        F: Fn(Context) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
     {
-        let command_name = command_name.to_string();
+        self.command_with_priority(command_name, 0, handler);
+    }
 
-        if command_name == "" {
-            eprintln!("[Error] Adding an empty command.");
-            return;
+    pub fn command_with_priority<F, Fut>(&mut self, command_name: &str, priority: i32, handler: F)
+    where
+        F: Fn(Context) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let command = CommandInfo::new(command_name);
+
+        if self.check_command(&command) {
+            let dispatcher = self.dispatcher_mut();
+            dispatcher.commands.push(command.clone());
+            dispatcher.add_listener(
+                EventListener::new(EventType::Command, handler, Some(command))
+                    .with_priority(priority),
+            );
         }
+    }
 
-        if !self.commands.contains(&command_name) {
-            self.commands.push(command_name.clone())
-        } else {
-            eprintln!("[Error] Adding a duplicate command {}.", command_name);
+    // Register a command with its metadata, which is shown by `/help`.
+    pub fn register(&mut self, command: Command) {
+        let (command, handler, priority) = command.into_parts();
+
+        if self.check_command(&command) {
+            let dispatcher = self.dispatcher_mut();
+            dispatcher.commands.push(command.clone());
+            dispatcher.add_listener(
+                EventListener::from_handler(EventType::Command, handler, Some(command))
+                    .with_priority(priority),
+            );
+        }
+    }
+
+    // Called when a handler or the global middleware returns an error or panics, instead of logging it.
+    pub fn on_error<F, Fut>(&mut self, hook: F)
+    where
+        F: Fn(Context, Error, ListenerInfo) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.dispatcher_mut().error_hook = Some(Arc::new(move |ctx, e, listener| {
+            Box::pin(hook(ctx, e, listener))
+        }));
+    }
+
+    // The reply sent to the user when a handler fails, only used without `on_error`.
+    pub fn set_error_reply(&mut self, reply: Option<&str>) {
+        self.dispatcher_mut().error_reply = reply.map(|reply| reply.to_string());
+    }
+
+    // Middleware run in the order they are added, around all the handlers of an event.
+    // Use `command.use_middleware(middleware)` for a single command.
+    pub fn use_middleware<M>(&mut self, middleware: M)
+    where
+        M: Middleware + 'static,
+    {
+        self.dispatcher_mut().middlewares.push(Arc::new(middleware));
+    }
+
+    // Share a value with all the handlers, which is got by `ctx.state::<T>()`,
+    // e.g. a database pool or the config of the app.
+    pub fn manage<T: Send + Sync + 'static>(&mut self, state: T) {
+        let states = &mut self.dispatcher_mut().states;
+
+        if states.contains_key(&TypeId::of::<T>()) {
+            eprintln!("[Error] Managing a duplicate state {}.", type_name::<T>());
             return;
         }
 
-        let event_type = EventType::from("command");
+        states.insert(TypeId::of::<T>(), Arc::new(state));
+    }
+
+    pub fn state<T: Send + Sync + 'static>(&self) -> Result<&T> {
+        self.dispatcher
+            .states
+            .get(&TypeId::of::<T>())
+            .and_then(|state| state.downcast_ref())
+            .ok_or_else(|| {
+                let msg = format!(
+                    "[Error] The state {} is not managed, add it by `bot.manage(state)`.",
+                    type_name::<T>()
+                );
+                Error::new(&msg)
+            })
+    }
+
+    fn is_registered(&self, name: &str) -> bool {
+        let dispatcher = &self.dispatcher;
+
+        dispatcher
+            .commands
+            .iter()
+            .any(|command| dispatcher.command_config.matches(command, name))
+    }
+
+    fn check_command(&self, command: &CommandInfo) -> bool {
+        if command.name.is_empty() {
+            eprintln!("[Error] Adding an empty command.");
+            return false;
+        }
+
+        for name in std::iter::once(&command.name).chain(&command.aliases) {
+            if self.is_registered(name) {
+                eprintln!("[Error] Adding a duplicate command {}.", name);
+                return false;
+            }
+        }
+
+        true
+    }
+
+    // The metadata of all the registered commands.
+    pub fn commands(&self) -> &[CommandInfo] {
+        &self.dispatcher.commands
+    }
+}
+
+async fn respond_webhook(req: WebhookRequest, webhook_reply: Option<WebhookReply>) {
+    let command = webhook_reply.and_then(|webhook_reply| webhook_reply.lock().unwrap().take());
+
+    if let Err(e) = req.respond(command).await {
+        eprintln!("[Error] Responding webhook request.\n{}", e);
+    }
+}
+
+// Turn a panic into an error.
+async fn catch_panic<F: Future<Output = Result<()>>>(future: F) -> Result<()> {
+    match AssertUnwindSafe(future).catch_unwind().await {
+        Ok(result) => result,
+        Err(panic) => Err(Error::new(&format!(
+            "[Error] Panicked: {}",
+            panic_message(&panic)
+        ))),
+    }
+}
+
+fn panic_message(panic: &Box<dyn Any + Send>) -> String {
+    if let Some(msg) = panic.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = panic.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+// Back off while the queue is idle, and reset once messages come.
+// The first idle poll still waits the base interval, and the wait doubles after that.
+fn poll_interval(base: Duration, max: Duration, idle_polls: u32) -> Duration {
+    let factor = 1u32 << idle_polls.saturating_sub(1).min(31);
+    base.saturating_mul(factor).min(max).max(base)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{poll_interval, Bot};
+    use crate::command::Command;
+    use crate::context::WebhookReply;
+    use crate::event::Event;
+    use crate::message::sender::FriendSender;
+    use crate::message::{MessageChain, SingleMessage};
+    use crate::scheduler::Scheduler;
+    use crate::{Adapter, BotConfig};
+    use crate::{BoxFuture, Context, Error, Middleware, Next};
+    use futures_util::{SinkExt, StreamExt};
+    use serde_json::Value;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::Notify;
+    use tokio::time::{sleep, timeout};
+    use tokio_tungstenite::accept_async;
+    use tokio_tungstenite::tungstenite::Message;
+
+    pub(crate) fn bot() -> Bot {
+        let config = BotConfig {
+            qq: 20211113,
+            master_qq: 20211114,
+            setting_file: "".to_string(),
+            adapter: Adapter::Http,
+        };
+
+        Bot::new(config, "session", "http://mirai.host")
+    }
+
+    pub(crate) fn friend_message(text: &str) -> Event {
+        Event::FriendMessage {
+            sender: FriendSender {
+                id: 20211114,
+                nickname: "Thungghuan".to_string(),
+                remark: "".to_string(),
+            },
+            message_chain: MessageChain::from(vec![
+                SingleMessage::Source { id: 7, time: 0 },
+                SingleMessage::Plain {
+                    text: text.to_string(),
+                },
+            ]),
+        }
+    }
+
+    // Dispatch the event, and return the text of the reply,
+    // which is kept by the webhook reply instead of being sent if it's the only one.
+    pub(crate) async fn dispatch(bot: &Bot, event: Event) -> Option<String> {
+        let reply: WebhookReply = Default::default();
+        let mut ctx = Context::new(bot.clone(), event).unwrap();
+        ctx.set_webhook_reply(reply.clone());
+        bot.dispatch_context(ctx).await;
+
+        let reply: Option<Value> = reply.lock().unwrap().take();
+        reply.map(|command| {
+            let message_chain: MessageChain =
+                serde_json::from_value(command["content"]["messageChain"].clone()).unwrap();
+            message_chain.plain_text()
+        })
+    }
+
+    // A fake mirai, which records the text of the sent messages.
+    async fn mirai_server() -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let sent = Arc::new(Mutex::new(vec![]));
+
+        let log = sent.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+
+                let mut req = vec![];
+                let mut chunk = [0; 4096];
+                let body = loop {
+                    let n = stream.read(&mut chunk).await.unwrap();
+                    req.extend_from_slice(&chunk[..n]);
+
+                    let text = String::from_utf8_lossy(&req).to_string();
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let len = head
+                            .lines()
+                            .find_map(|line| {
+                                line.to_lowercase()
+                                    .strip_prefix("content-length: ")
+                                    .map(|len| len.parse::<usize>().unwrap())
+                            })
+                            .unwrap_or(0);
+                        if body.len() >= len {
+                            break body.to_string();
+                        }
+                    }
+                };
+
+                let body: Value = serde_json::from_str(&body).unwrap();
+                let message_chain: MessageChain =
+                    serde_json::from_value(body["messageChain"].clone()).unwrap();
+                log.lock().unwrap().push(message_chain.plain_text());
+
+                let body = r#"{"code":0,"msg":"success","messageId":7}"#;
+                let resp = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(resp.as_bytes()).await.unwrap();
+            }
+        });
+
+        (format!("http://{}", address), sent)
+    }
+
+    #[tokio::test]
+    async fn keep_webhook_replies_in_order() {
+        let (base_url, sent) = mirai_server().await;
+        let config = BotConfig {
+            qq: 20211113,
+            master_qq: 20211114,
+            setting_file: "".to_string(),
+            adapter: Adapter::Http,
+        };
+
+        let mut bot = Bot::new(config, "session", &base_url);
+        bot.on("message", |ctx| async move {
+            ctx.reply(MessageChain::from("a")).await?;
+            ctx.reply(MessageChain::from("b")).await?;
+            Ok(())
+        });
+        bot.command("once", |ctx| async move {
+            ctx.reply(MessageChain::from("c")).await?;
+            Ok(())
+        });
+
+        // the held reply is sent before the second one, instead of in the response
+        assert_eq!(dispatch(&bot, friend_message("hi")).await, None);
+        assert_eq!(sent.lock().unwrap().clone(), vec!["a", "b"]);
+
+        // the only reply is still returned in the response
+        assert_eq!(dispatch(&bot, friend_message("/once")).await.unwrap(), "c");
+        assert_eq!(sent.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn answer_help() {
+        let mut bot = bot();
+        bot.register(
+            Command::new("ban", |_| async { Ok(()) })
+                .aliases(&["b"])
+                .desc("Ban a member."),
+        );
+
+        assert_eq!(
+            dispatch(&bot, friend_message("/help")).await.unwrap(),
+            "Commands:\n/ban - Ban a member.\nSend `/help <command>` for more details."
+        );
+        assert_eq!(
+            dispatch(&bot, friend_message("/help b")).await.unwrap(),
+            "/ban\nBan a member.\nAliases: /b"
+        );
+
+        // the usage is replied when the arguments are invalid
+        bot.register(Command::with_args::<(i64,), _, _>(
+            "double",
+            |ctx, (n,)| async move {
+                ctx.reply(MessageChain::from((n * 2).to_string())).await?;
+                Ok(())
+            },
+        ));
+
+        assert_eq!(
+            dispatch(&bot, friend_message("/double 21")).await.unwrap(),
+            "42"
+        );
+        assert_eq!(
+            dispatch(&bot, friend_message("/double x")).await.unwrap(),
+            "Usage: /double <number>\nInvalid argument `x`, expected <number>."
+        );
+    }
+
+    #[tokio::test]
+    async fn dispatch_subcommands() {
+        let mut bot = bot();
+        bot.register(
+            Command::group("todo")
+                .desc("Manage the todo list.")
+                .subcommand(
+                    Command::with_args::<(String,), _, _>("add", |ctx, (text,)| async move {
+                        let reply = format!("{}: {}", ctx.command_name(), text);
+                        ctx.reply(MessageChain::from(reply)).await?;
+                        Ok(())
+                    })
+                    .desc("Add a todo."),
+                )
+                .subcommand(Command::with_args::<(u32,), _, _>("done", |_, _| async {
+                    Ok(())
+                })),
+        );
+
+        assert_eq!(
+            dispatch(&bot, friend_message("/todo add milk"))
+                .await
+                .unwrap(),
+            "todo add: milk"
+        );
+        assert_eq!(
+            dispatch(&bot, friend_message("/todo ad milk"))
+                .await
+                .unwrap(),
+            "Unknown subcommand `ad` of `/todo`, did you mean `/todo add`?"
+        );
+        assert_eq!(
+            dispatch(&bot, friend_message("/todo done x"))
+                .await
+                .unwrap(),
+            "Usage: /todo done <number>\nInvalid argument `x`, expected <number>."
+        );
+        assert_eq!(
+            dispatch(&bot, friend_message("/todo")).await.unwrap(),
+            "/todo\nManage the todo list.\nSubcommands:\n/todo add - Add a todo.\n/todo done"
+        );
+    }
+
+    struct Log(Arc<Mutex<Vec<String>>>, &'static str);
+
+    impl Middleware for Log {
+        fn call<'a>(&'a self, ctx: Context, next: Next<'a>) -> BoxFuture<'a> {
+            Box::pin(async move {
+                self.0.lock().unwrap().push(format!("{} before", self.1));
+                next.run(ctx).await?;
+                self.0.lock().unwrap().push(format!("{} after", self.1));
+                Ok(())
+            })
+        }
+    }
+
+    struct Tag(&'static str);
+
+    fn tag<'a>(mut ctx: Context, next: Next<'a>) -> BoxFuture<'a> {
+        ctx.insert_extension(Tag("tagged"));
+        next.run(ctx)
+    }
+
+    fn deny<'a>(ctx: Context, _next: Next<'a>) -> BoxFuture<'a> {
+        Box::pin(async move {
+            ctx.reply(MessageChain::from("Permission denied.")).await?;
+            Ok(())
+        })
+    }
+
+    #[tokio::test]
+    async fn run_middlewares() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let handler_log = log.clone();
+
+        let mut bot = bot();
+        bot.use_middleware(Log(log.clone(), "global"));
+        bot.use_middleware(tag);
+        bot.register(
+            Command::new("hi", move |ctx| {
+                let log = handler_log.clone();
+                async move {
+                    let tag = ctx.extension::<Tag>().map_or("", |tag| tag.0);
+                    log.lock().unwrap().push(format!("hi {}", tag));
+                    Ok(())
+                }
+            })
+            .use_middleware(Log(log.clone(), "hi")),
+        );
+        bot.register(Command::new("secret", |_| async { Ok(()) }).use_middleware(deny));
+
+        assert_eq!(dispatch(&bot, friend_message("/hi")).await, None);
+        assert_eq!(
+            log.lock().unwrap().drain(..).collect::<Vec<String>>(),
+            vec![
+                "global before",
+                "hi before",
+                "hi tagged",
+                "hi after",
+                "global after"
+            ]
+        );
+
+        assert_eq!(
+            dispatch(&bot, friend_message("/secret")).await.unwrap(),
+            "Permission denied."
+        );
+    }
+
+    #[tokio::test]
+    async fn report_handler_errors() {
+        let mut bot = bot();
+        bot.set_error_reply(Some("Something went wrong."));
+        bot.register(Command::new("fail", |_| async {
+            Err(Error::new("[Error] Failed."))
+        }));
+        bot.register(Command::new("panic", |_| async { panic!("boom") }));
+
+        assert_eq!(
+            dispatch(&bot, friend_message("/fail")).await.unwrap(),
+            "Something went wrong."
+        );
+        assert_eq!(
+            dispatch(&bot, friend_message("/panic")).await.unwrap(),
+            "Something went wrong."
+        );
+
+        // the hook gets the failed listener, and the following listeners still run
+        let errors = Arc::new(Mutex::new(vec![]));
+        let hook_errors = errors.clone();
+
+        bot.on("message", |_| async { Err(Error::new("[Error] Failed.")) });
+        bot.on("message", |ctx| async move {
+            ctx.reply(MessageChain::from("Still running.")).await?;
+            Ok(())
+        });
+        bot.on_error(move |_, e, listener| {
+            hook_errors
+                .lock()
+                .unwrap()
+                .push((listener.to_string(), e == Error::new("[Error] Failed.")));
+            async { Ok(()) }
+        });
+
+        assert_eq!(
+            dispatch(&bot, friend_message("hello")).await.unwrap(),
+            "Still running."
+        );
+        dispatch(&bot, friend_message("/panic")).await;
+        assert_eq!(
+            errors.lock().unwrap().clone(),
+            vec![
+                ("the listener #2 of `message`".to_string(), true),
+                ("the command `panic`".to_string(), false)
+            ]
+        );
+    }
+
+    fn fail<'a>(ctx: Context, next: Next<'a>) -> BoxFuture<'a> {
+        Box::pin(async move {
+            if ctx.message_chain() == MessageChain::from("panic") {
+                panic!("boom");
+            }
+            next.run(ctx).await?;
+            Err(Error::new("[Error] Failed."))
+        })
+    }
+
+    #[tokio::test]
+    async fn report_middleware_and_help_errors() {
+        let errors = Arc::new(Mutex::new(vec![]));
+        let hook_errors = errors.clone();
+
+        let mut bot = bot();
+        bot.on_error(move |_, e, listener| {
+            hook_errors.lock().unwrap().push((listener.to_string(), e));
+            async { Ok(()) }
+        });
+
+        // `/help "x` has an unclosed quote
+        dispatch(&bot, friend_message("/help \"x")).await;
+        assert_eq!(
+            errors.lock().unwrap().pop().unwrap().0,
+            "the command `help`"
+        );
+
+        bot.use_middleware(fail);
+        dispatch(&bot, friend_message("hello")).await;
+        dispatch(&bot, friend_message("panic")).await;
+        assert_eq!(
+            errors.lock().unwrap().clone(),
+            vec![
+                (
+                    "the middleware of `friendMessage`".to_string(),
+                    Error::new("[Error] Failed.")
+                ),
+                (
+                    "the middleware of `friendMessage`".to_string(),
+                    Error::new("[Error] Panicked: boom")
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn spawn_handlers() {
+        let notify = Arc::new(Notify::new());
+        let (wait, go) = (notify.clone(), notify.clone());
+
+        let mut bot = bot();
+        bot.register(Command::new("wait", move |_| {
+            let notify = wait.clone();
+            async move {
+                notify.notified().await;
+                Ok(())
+            }
+        }));
+        bot.register(Command::new("go", move |_| {
+            let notify = go.clone();
+            async move {
+                notify.notify_one();
+                Ok(())
+            }
+        }));
+
+        // `/wait` doesn't block `/go`, which wakes it up
+        let mut scheduler = Scheduler::new(2, false);
+        let wait = scheduler
+            .spawn(&bot, friend_message("/wait"))
+            .await
+            .unwrap();
+        let go = scheduler.spawn(&bot, friend_message("/go")).await.unwrap();
+
+        timeout(Duration::from_secs(1), async {
+            wait.await.unwrap();
+            go.await.unwrap();
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn keep_order_in_chat() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let handler_log = log.clone();
+
+        let mut bot = bot();
+        bot.register(Command::with_args::<(u64,), _, _>(
+            "sleep",
+            move |_, (ms,)| {
+                let log = handler_log.clone();
+                async move {
+                    sleep(Duration::from_millis(ms)).await;
+                    log.lock().unwrap().push(ms);
+                    Ok(())
+                }
+            },
+        ));
+
+        let mut scheduler = Scheduler::new(4, true);
+        let mut tasks = vec![];
+        for ms in [30, 10, 0] {
+            let event = friend_message(&format!("/sleep {}", ms));
+            tasks.push(scheduler.spawn(&bot, event).await.unwrap());
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(log.lock().unwrap().clone(), vec![30, 10, 0]);
+    }
+
+    struct Counter(AtomicUsize);
+
+    #[tokio::test]
+    async fn share_states() {
+        let greeting = Arc::new("Hello".to_string());
+
+        let mut bot = bot();
+        bot.manage(Counter(AtomicUsize::new(0)));
+        bot.on("message", move |ctx| {
+            let greeting = greeting.clone();
+            async move {
+                let count = ctx.state::<Counter>()?.0.fetch_add(1, Ordering::SeqCst) + 1;
+                let reply = format!("{} #{}", greeting, count);
+                ctx.reply(MessageChain::from(reply)).await?;
+                Ok(())
+            }
+        });
+        bot.command("missing", |ctx| async move {
+            ctx.state::<String>()?;
+            Ok(())
+        });
+        bot.set_error_reply(Some("Something went wrong."));
+
+        assert_eq!(
+            dispatch(&bot, friend_message("hi")).await.unwrap(),
+            "Hello #1"
+        );
+        assert_eq!(
+            dispatch(&bot, friend_message("hi")).await.unwrap(),
+            "Hello #2"
+        );
+        assert_eq!(bot.state::<Counter>().unwrap().0.load(Ordering::SeqCst), 2);
+
+        assert_eq!(
+            dispatch(&bot, friend_message("/missing")).await.unwrap(),
+            "Something went wrong."
+        );
+    }
+
+    type Called = Arc<Mutex<Vec<&'static str>>>;
+
+    fn record(log: &Called, name: &'static str) -> impl Fn(Context) -> BoxFuture<'static> {
+        let log = log.clone();
+
+        move |_| {
+            log.lock().unwrap().push(name);
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn order_listeners() {
+        let log: Called = Default::default();
+
+        let mut bot = bot();
+        bot.on_with_priority("message", 0, record(&log, "a"));
+        bot.on_with_priority("message", 10, record(&log, "b"));
+        bot.on_with_priority("message", -5, record(&log, "fallback"));
+        bot.on_with_priority("friendMessage", 10, record(&log, "c"));
+        bot.on("message", record(&log, "d"));
+
+        dispatch(&bot, friend_message("hi")).await;
+        assert_eq!(
+            log.lock().unwrap().drain(..).collect::<Vec<_>>(),
+            vec!["b", "c", "a", "d", "fallback"]
+        );
+
+        // the spam filter runs first and stops the others
+        bot.on_with_priority("message", 100, |ctx| async move {
+            if ctx.message_chain().plain_text().contains("spam") {
+                ctx.stop_propagation();
+            }
+            Ok(())
+        });
+
+        dispatch(&bot, friend_message("spam")).await;
+        assert!(log.lock().unwrap().is_empty());
+
+        dispatch(&bot, friend_message("hi")).await;
+        assert_eq!(
+            log.lock().unwrap().drain(..).collect::<Vec<_>>(),
+            vec!["b", "c", "a", "d", "fallback"]
+        );
+
+        // a middleware can stop all the listeners too
+        bot.use_middleware(stop);
+        dispatch(&bot, friend_message("hi")).await;
+        assert!(log.lock().unwrap().is_empty());
+        assert_eq!(dispatch(&bot, friend_message("/help")).await, None);
+    }
+
+    #[tokio::test]
+    async fn stop_help_and_prioritise_commands() {
+        let log: Called = Default::default();
+
+        let mut bot = bot();
+        bot.on_with_priority("command", -5, record(&log, "fallback"));
+        bot.on("command", record(&log, "unknown"));
+
+        // `/help` is answered before the other listeners with priority 0
+        assert_eq!(
+            dispatch(&bot, friend_message("/help")).await.unwrap(),
+            "No command is available."
+        );
+        assert!(log.lock().unwrap().is_empty());
+
+        bot.on_with_priority("command", 10, |ctx| async move {
+            if ctx.sender_id() == 20211114 {
+                ctx.stop_propagation();
+            }
+            Ok(())
+        });
+        assert_eq!(dispatch(&bot, friend_message("/help")).await, None);
+
+        let priorities = Arc::new(Mutex::new(vec![]));
+        let hook_priorities = priorities.clone();
+
+        bot.command_with_priority("a", 5, |_| async { Err(Error::new("[Error] A.")) });
+        bot.register(Command::new("b", |_| async { Err(Error::new("[Error] B.")) }).priority(-1));
+        bot.on_error(move |_, _, listener| {
+            hook_priorities
+                .lock()
+                .unwrap()
+                .push((listener.to_string(), listener.priority));
+            async { Ok(()) }
+        });
+
+        dispatch(&bot, friend_message("/a")).await;
+        dispatch(&bot, friend_message("/b")).await;
+        assert_eq!(
+            priorities.lock().unwrap().clone(),
+            vec![
+                ("the command `a`".to_string(), 5),
+                ("the command `b`".to_string(), -1)
+            ]
+        );
+    }
+
+    fn stop<'a>(ctx: Context, next: Next<'a>) -> BoxFuture<'a> {
+        ctx.stop_propagation();
+        next.run(ctx)
+    }
+
+    #[tokio::test]
+    async fn reconnect_ws() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = accept_async(stream).await.unwrap();
+            let event = r#"{"syncId":"-1","data":{"type":"BotOnlineEvent","qq":20211113}}"#;
+            ws.send(Message::Text(event.to_string())).await.unwrap();
+
+            // keep the connection open
+            while ws.next().await.is_some() {}
+        });
+
+        let url = format!("ws://{}/all?verifyKey=verify-key&qq=20211113", address);
+        let (_ws_client, mut events) = timeout(Duration::from_secs(5), bot().reconnect(&url))
+            .await
+            .unwrap();
+
+        assert_eq!(events.recv().await.unwrap()["type"], "BotOnlineEvent");
+    }
+
+    #[test]
+    fn check_poll_interval() {
+        let base = Duration::from_secs(1);
+        let max = Duration::from_secs(8);
+
+        let intervals: Vec<u64> = (1..=6)
+            .map(|idle_polls| poll_interval(base, max, idle_polls).as_secs())
+            .collect();
+        assert_eq!(intervals, vec![1, 2, 4, 8, 8, 8]);
 
-        self.event_listeners
-            .push(EventListener::new(event_type, handler, Some(command_name)));
+        assert_eq!(poll_interval(base, max, 0), base);
+        assert_eq!(poll_interval(base, max, u32::MAX), max);
     }
 }
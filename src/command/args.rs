@@ -0,0 +1,628 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::message::{MessageChain, SingleMessage};
+
+// An argument of a command, either a piece of text
+// or a message element such as a mention or an image.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Arg {
+    Text(String),
+    Message(SingleMessage),
+}
+
+impl Display for Arg {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Arg::Text(text) => write!(f, "{}", text),
+            Arg::Message(SingleMessage::At { target, .. }) => write!(f, "@{}", target),
+            Arg::Message(SingleMessage::Image { .. }) => write!(f, "[image]"),
+            Arg::Message(SingleMessage::Face { face_id, .. }) => write!(f, "[face:{}]", face_id),
+            Arg::Message(_) => write!(f, "[message]"),
+        }
+    }
+}
+
+// The error shown to the user when the arguments can't be parsed,
+// which tells how to use the command.
+#[derive(Debug, PartialEq, Clone)]
+pub struct UsageError {
+    pub prefix: String,
+    pub command: String,
+    pub usage: String,
+    pub reason: String,
+}
+
+impl Display for UsageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        if self.usage.is_empty() {
+            write!(f, "Usage: {}{}\n{}", self.prefix, self.command, self.reason)
+        } else {
+            write!(
+                f,
+                "Usage: {}{} {}\n{}",
+                self.prefix, self.command, self.usage, self.reason
+            )
+        }
+    }
+}
+
+impl From<UsageError> for Error {
+    fn from(err: UsageError) -> Self {
+        Error::new(&err.to_string())
+    }
+}
+
+// A value which can be parsed from a single argument.
+pub trait FromArg: Sized {
+    // The placeholder in the usage, e.g. `number` in `<number>`.
+    fn name() -> String;
+
+    // Return the reason if the argument is invalid.
+    fn from_arg(arg: &Arg) -> Result<Self, String>;
+}
+
+fn invalid<T: FromArg>(arg: &Arg) -> String {
+    format!("Invalid argument `{}`, expected <{}>.", arg, T::name())
+}
+
+macro_rules! from_arg_for_numbers {
+    ($($t:ty),*) => {
+        $(
+            impl FromArg for $t {
+                fn name() -> String {
+                    "number".to_string()
+                }
+
+                fn from_arg(arg: &Arg) -> Result<Self, String> {
+                    match arg {
+                        Arg::Text(text) => text.parse().map_err(|_| invalid::<Self>(arg)),
+                        _ => Err(invalid::<Self>(arg)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+from_arg_for_numbers!(i32, i64, u32, u64, usize, f64);
+
+impl FromArg for String {
+    fn name() -> String {
+        "text".to_string()
+    }
+
+    fn from_arg(arg: &Arg) -> Result<Self, String> {
+        match arg {
+            Arg::Text(text) => Ok(text.clone()),
+            _ => Err(invalid::<Self>(arg)),
+        }
+    }
+}
+
+impl FromArg for bool {
+    fn name() -> String {
+        "yes|no".to_string()
+    }
+
+    fn from_arg(arg: &Arg) -> Result<Self, String> {
+        match arg {
+            Arg::Text(text) => match text.to_lowercase().as_str() {
+                "yes" | "y" | "true" | "on" | "1" => Ok(true),
+                "no" | "n" | "false" | "off" | "0" => Ok(false),
+                _ => Err(invalid::<Self>(arg)),
+            },
+            _ => Err(invalid::<Self>(arg)),
+        }
+    }
+}
+
+// Durations like `30s`, `10m`, `1h30m` and `2d`, a plain number is in seconds.
+impl FromArg for Duration {
+    fn name() -> String {
+        "duration".to_string()
+    }
+
+    fn from_arg(arg: &Arg) -> Result<Self, String> {
+        let text = match arg {
+            Arg::Text(text) => text,
+            _ => return Err(invalid::<Self>(arg)),
+        };
+
+        if let Ok(secs) = text.parse::<u64>() {
+            return Ok(Duration::from_secs(secs));
+        }
+
+        let mut secs = 0;
+        let mut number = String::new();
+
+        for c in text.chars() {
+            if c.is_ascii_digit() {
+                number.push(c);
+                continue;
+            }
+
+            let unit = match c {
+                's' => 1,
+                'm' => 60,
+                'h' => 60 * 60,
+                'd' => 24 * 60 * 60,
+                _ => return Err(invalid::<Self>(arg)),
+            };
+            let n: u64 = number.parse().map_err(|_| invalid::<Self>(arg))?;
+
+            secs = n
+                .checked_mul(unit)
+                .and_then(|n| n.checked_add(secs))
+                .ok_or_else(|| invalid::<Self>(arg))?;
+            number.clear();
+        }
+
+        if !number.is_empty() || text.is_empty() {
+            return Err(invalid::<Self>(arg));
+        }
+
+        Ok(Duration::from_secs(secs))
+    }
+}
+
+// A mentioned member, which can also be given as `@qq` or `qq` in text.
+#[derive(Debug, PartialEq, Clone)]
+pub struct At(pub i64);
+
+impl FromArg for At {
+    fn name() -> String {
+        "@member".to_string()
+    }
+
+    fn from_arg(arg: &Arg) -> Result<Self, String> {
+        match arg {
+            Arg::Message(SingleMessage::At { target, .. }) => Ok(At(*target)),
+            Arg::Text(text) => text
+                .trim_start_matches('@')
+                .parse()
+                .map(At)
+                .map_err(|_| invalid::<Self>(arg)),
+            _ => Err(invalid::<Self>(arg)),
+        }
+    }
+}
+
+// An image sent as an argument, which can be sent again directly.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Image(pub SingleMessage);
+
+impl FromArg for Image {
+    fn name() -> String {
+        "image".to_string()
+    }
+
+    fn from_arg(arg: &Arg) -> Result<Self, String> {
+        match arg {
+            Arg::Message(
+                message @ (SingleMessage::Image { .. } | SingleMessage::FlashImage { .. }),
+            ) => Ok(Image(message.clone())),
+            _ => Err(invalid::<Self>(arg)),
+        }
+    }
+}
+
+// Define an enum which can be parsed from the arguments, case insensitive.
+//
+// ```
+// wood::arg_enum! {
+//     pub enum Switch {
+//         On = "on",
+//         Off = "off",
+//     }
+// }
+// ```
+#[macro_export]
+macro_rules! arg_enum {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $($variant:ident = $value:literal),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $($variant,)*
+        }
+
+        impl $crate::command::FromArg for $name {
+            fn name() -> String {
+                [$($value),*].join("|")
+            }
+
+            fn from_arg(arg: &$crate::command::Arg) -> Result<Self, String> {
+                if let $crate::command::Arg::Text(text) = arg {
+                    $(
+                        if text.eq_ignore_ascii_case($value) {
+                            return Ok($name::$variant);
+                        }
+                    )*
+                }
+
+                Err(format!(
+                    "Invalid argument `{}`, expected <{}>.",
+                    arg,
+                    <Self as $crate::command::FromArg>::name()
+                ))
+            }
+        }
+    };
+}
+
+// A positional parameter, which is required, optional (`Option<T>`),
+// or takes all the rest arguments (`Vec<T>`).
+pub trait Param: Sized {
+    fn usage() -> String;
+
+    fn take(args: &[Arg], index: &mut usize) -> Result<Self, String>;
+}
+
+impl<T: FromArg> Param for T {
+    fn usage() -> String {
+        format!("<{}>", T::name())
+    }
+
+    fn take(args: &[Arg], index: &mut usize) -> Result<Self, String> {
+        match args.get(*index) {
+            Some(arg) => {
+                *index += 1;
+                T::from_arg(arg)
+            }
+            None => Err(format!("Missing argument <{}>.", T::name())),
+        }
+    }
+}
+
+impl<T: FromArg> Param for Option<T> {
+    fn usage() -> String {
+        format!("[{}]", T::name())
+    }
+
+    fn take(args: &[Arg], index: &mut usize) -> Result<Self, String> {
+        match args.get(*index) {
+            Some(arg) => {
+                *index += 1;
+                T::from_arg(arg).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl<T: FromArg> Param for Vec<T> {
+    fn usage() -> String {
+        format!("[{}]...", T::name())
+    }
+
+    fn take(args: &[Arg], index: &mut usize) -> Result<Self, String> {
+        let rest = args[(*index).min(args.len())..]
+            .iter()
+            .map(T::from_arg)
+            .collect();
+        *index = args.len();
+
+        rest
+    }
+}
+
+// All the positional arguments of a command, implemented for tuples of `Param`.
+pub trait FromArgs: Sized {
+    fn usage() -> String;
+
+    fn from_args(args: &[Arg]) -> Result<Self, String>;
+}
+
+macro_rules! from_args_for_tuples {
+    ($(($($t:ident),*)),*) => {
+        $(
+            impl<$($t: Param),*> FromArgs for ($($t,)*) {
+                fn usage() -> String {
+                    let usage: Vec<String> = vec![$($t::usage()),*];
+                    usage.join(" ")
+                }
+
+                #[allow(unused_mut, unused_variables)]
+                fn from_args(args: &[Arg]) -> Result<Self, String> {
+                    let mut index = 0;
+                    let params = ($($t::take(args, &mut index)?,)*);
+
+                    match args.get(index) {
+                        Some(arg) => Err(format!("Too many arguments, unexpected `{}`.", arg)),
+                        None => Ok(params),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+from_args_for_tuples!(
+    (),
+    (A),
+    (A, B),
+    (A, B, C),
+    (A, B, C, D),
+    (A, B, C, D, E),
+    (A, B, C, D, E, F)
+);
+
+// The parsed arguments of a command.
+//
+// Unquoted `--name` and `-n` are flags, `--name=value` are options,
+// and the others are positional arguments. Everything after `--` is positional.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Args {
+    prefix: String,
+    command: String,
+    positional: Vec<Arg>,
+    flags: HashSet<String>,
+    options: HashMap<String, Arg>,
+}
+
+impl Args {
+    pub fn parse(command: &str, message_chain: &MessageChain) -> Result<Self, UsageError> {
+        Args::parse_with_prefix("/", command, message_chain)
+    }
+
+    // The prefix is only used to show the usage.
+    pub fn parse_with_prefix(
+        prefix: &str,
+        command: &str,
+        message_chain: &MessageChain,
+    ) -> Result<Self, UsageError> {
+        let mut args = Args {
+            prefix: prefix.to_string(),
+            command: command.to_string(),
+            ..Default::default()
+        };
+        let tokens = tokenize(message_chain).map_err(|reason| args.error("", reason))?;
+
+        let mut options_end = false;
+        for (arg, quoted) in tokens {
+            let text = match &arg {
+                Arg::Text(text) if !quoted && !options_end => text.clone(),
+                _ => {
+                    args.positional.push(arg);
+                    continue;
+                }
+            };
+
+            if text == "--" {
+                options_end = true;
+            } else if let Some(long) = text.strip_prefix("--") {
+                match long.split_once('=') {
+                    Some((name, value)) => {
+                        args.options
+                            .insert(name.to_string(), Arg::Text(value.to_string()));
+                    }
+                    None => {
+                        args.flags.insert(long.to_string());
+                    }
+                }
+            } else if text.len() > 1
+                && text.starts_with('-')
+                && text[1..].chars().all(|c| c.is_ascii_alphabetic())
+            {
+                // `-abc` is the same as `-a -b -c`, while `-5` is a number
+                for c in text[1..].chars() {
+                    args.flags.insert(c.to_string());
+                }
+            } else {
+                args.positional.push(arg);
+            }
+        }
+
+        Ok(args)
+    }
+
+    fn error(&self, usage: &str, reason: String) -> UsageError {
+        UsageError {
+            prefix: self.prefix.clone(),
+            command: self.command.clone(),
+            usage: usage.to_string(),
+            reason,
+        }
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    pub fn positional(&self) -> &[Arg] {
+        &self.positional
+    }
+
+    pub fn len(&self) -> usize {
+        self.positional.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positional.is_empty()
+    }
+
+    // Both `flag("verbose")` for `--verbose` and `flag("v")` for `-v`.
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.contains(name)
+    }
+
+    pub fn option<T: FromArg>(&self, name: &str) -> Result<Option<T>, UsageError> {
+        match self.options.get(name) {
+            Some(arg) => T::from_arg(arg)
+                .map(Some)
+                .map_err(|reason| self.error(&format!("--{}=<{}>", name, T::name()), reason)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get<T: Param>(&self, index: usize) -> Result<T, UsageError> {
+        let mut i = index;
+
+        T::take(&self.positional, &mut i).map_err(|reason| self.error(&T::usage(), reason))
+    }
+
+    // Parse all the positional arguments into a tuple, e.g. `(At, Duration)`.
+    pub fn parse_into<T: FromArgs>(&self) -> Result<T, UsageError> {
+        T::from_args(&self.positional).map_err(|reason| self.error(&T::usage(), reason))
+    }
+}
+
+// Split the message chain into arguments, telling whether each one is quoted.
+fn tokenize(message_chain: &MessageChain) -> Result<Vec<(Arg, bool)>, String> {
+    let mut tokens = vec![];
+
+    for message in message_chain.iter() {
+        match message {
+            SingleMessage::Plain { text } => tokenize_text(text, &mut tokens)?,
+            message => tokens.push((Arg::Message(message.clone()), false)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn tokenize_text(text: &str, tokens: &mut Vec<(Arg, bool)>) -> Result<(), String> {
+    let mut chars = text.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+        if chars.peek().is_none() {
+            return Ok(());
+        }
+
+        let mut token = String::new();
+        let mut quoted = false;
+
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+            match c {
+                '"' | '\'' => {
+                    quoted = true;
+
+                    loop {
+                        match chars.next() {
+                            Some(end) if end == c => break,
+                            // only escape in double quotes
+                            Some('\\') if c == '"' => match chars.next() {
+                                Some(escaped) => token.push(escaped),
+                                None => return Err("Unclosed quote in arguments.".to_string()),
+                            },
+                            Some(other) => token.push(other),
+                            None => return Err("Unclosed quote in arguments.".to_string()),
+                        }
+                    }
+                }
+                '\\' => match chars.next() {
+                    Some(escaped) => token.push(escaped),
+                    None => token.push('\\'),
+                },
+                _ => token.push(c),
+            }
+        }
+
+        tokens.push((Arg::Text(token), quoted));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Arg, Args, At, FromArg, Image};
+    use crate::message::{MessageChain, SingleMessage};
+    use std::time::Duration;
+
+    crate::arg_enum! {
+        #[derive(Debug, PartialEq)]
+        enum Switch {
+            On = "on",
+            Off = "off",
+        }
+    }
+
+    #[test]
+    fn check_parse_args() {
+        let message_chain = MessageChain::new()
+            .text("  \"hello  world\" --force -ab --count=3 -5 ")
+            .at(20211113)
+            .text(" 'it''s' -- --not-flag")
+            .image_url("https://mirai.host/image.png");
+
+        let args = Args::parse("test", &message_chain).unwrap();
+
+        assert_eq!(
+            args.positional(),
+            &[
+                Arg::Text("hello  world".to_string()),
+                Arg::Text("-5".to_string()),
+                Arg::Message(SingleMessage::At {
+                    target: 20211113,
+                    display: "".to_string()
+                }),
+                Arg::Text("its".to_string()),
+                Arg::Text("--not-flag".to_string()),
+                Arg::Message(SingleMessage::Image {
+                    image_id: None,
+                    url: Some("https://mirai.host/image.png".to_string()),
+                    path: None,
+                    base64: None,
+                }),
+            ]
+        );
+        assert!(args.flag("force") && args.flag("a") && args.flag("b"));
+        assert!(!args.flag("c"));
+        assert_eq!(args.option::<i64>("count").unwrap(), Some(3));
+        assert_eq!(args.get::<i64>(1).unwrap(), -5);
+        assert_eq!(args.get::<At>(2).unwrap(), At(20211113));
+        assert!(matches!(args.get::<Image>(5), Ok(Image(_))));
+    }
+
+    #[test]
+    fn check_parse_into() {
+        let message_chain = MessageChain::new().at(20211113).text(" 1h30m ON");
+        let args = Args::parse("mute", &message_chain).unwrap();
+
+        let (at, duration, switch, rest): (At, Duration, Option<Switch>, Vec<String>) =
+            args.parse_into().unwrap();
+        assert_eq!(at, At(20211113));
+        assert_eq!(duration, Duration::from_secs(90 * 60));
+        assert_eq!(switch, Some(Switch::On));
+        assert!(rest.is_empty());
+
+        let err = args.parse_into::<(At, i64)>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Usage: /mute <@member> <number>\nInvalid argument `1h30m`, expected <number>."
+        );
+
+        let err = args.parse_into::<(At, Duration)>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Usage: /mute <@member> <duration>\nToo many arguments, unexpected `ON`."
+        );
+
+        let err = args.parse_into::<(At, Duration, Switch, At)>().unwrap_err();
+        assert_eq!(err.reason, "Missing argument <@member>.");
+
+        assert!(Args::parse("mute", &MessageChain::from("\"unclosed")).is_err());
+    }
+
+    #[test]
+    fn check_duration() {
+        let duration = |text: &str| Duration::from_arg(&Arg::Text(text.to_string()));
+
+        assert_eq!(duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(duration("1h30m"), Ok(Duration::from_secs(90 * 60)));
+        assert!(duration("1x").is_err());
+
+        // too long to be counted in seconds
+        assert_eq!(
+            duration("999999999999999d"),
+            Err("Invalid argument `999999999999999d`, expected <duration>.".to_string())
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use super::CommandInfo;
+
+// How commands are recognised, set by `bot.set_command_config(config)`.
+//
+// By default, commands start with `/`, and an @ to the bot is required in groups.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CommandConfig {
+    prefixes: Vec<String>,
+    require_at: bool,
+    group_require_at: HashMap<i64, bool>,
+    nicknames: Vec<String>,
+    case_sensitive: bool,
+}
+
+impl Default for CommandConfig {
+    fn default() -> Self {
+        CommandConfig {
+            prefixes: vec!["/".to_string()],
+            require_at: true,
+            group_require_at: HashMap::new(),
+            nicknames: vec![],
+            case_sensitive: true,
+        }
+    }
+}
+
+// A command found in a message, e.g. `!` and `ban` in `!ban @member 1h`.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct CommandMatch<'a> {
+    pub prefix: &'a str,
+    pub name: String,
+    pub content: &'a str,
+}
+
+impl CommandConfig {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    // The longest matched prefix is used, so `!!` and `!` can be used together.
+    // Empty prefixes are ignored, and the prefixes are kept if none is left,
+    // otherwise no command could match.
+    pub fn prefixes(mut self, prefixes: &[&str]) -> Self {
+        let prefixes: Vec<String> = prefixes
+            .iter()
+            .filter(|prefix| !prefix.is_empty())
+            .map(|prefix| prefix.to_string())
+            .collect();
+
+        if !prefixes.is_empty() {
+            self.prefixes = prefixes;
+        }
+        self
+    }
+
+    // Whether an @ to the bot is required for commands in groups.
+    pub fn require_at(mut self, require_at: bool) -> Self {
+        self.require_at = require_at;
+        self
+    }
+
+    // Override `require_at` for a group, e.g. a group with only the bot and its master.
+    pub fn group_require_at(mut self, group: i64, require_at: bool) -> Self {
+        self.group_require_at.insert(group, require_at);
+        self
+    }
+
+    // A message starting with the nickname and followed by a command is treated as an @ to the bot,
+    // e.g. `wood, /help` or `wood /help`, while `wood is nice` is just a message.
+    pub fn nickname(mut self, nickname: &str) -> Self {
+        if !nickname.is_empty() {
+            self.nicknames.push(nickname.to_string());
+        }
+        self
+    }
+
+    // Whether `/Help` is different from `/help`, the prefixes and nicknames are affected too.
+    pub fn case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive = case_sensitive;
+        self
+    }
+
+    // The first prefix, which is shown in `/help` and the usage.
+    pub fn prefix(&self) -> &str {
+        self.prefixes.first().map_or("/", |prefix| prefix.as_str())
+    }
+
+    pub fn is_require_at(&self, group: i64) -> bool {
+        *self
+            .group_require_at
+            .get(&group)
+            .unwrap_or(&self.require_at)
+    }
+
+    pub fn is_case_sensitive(&self) -> bool {
+        self.case_sensitive
+    }
+
+    pub(crate) fn name_eq(&self, a: &str, b: &str) -> bool {
+        if self.case_sensitive {
+            a == b
+        } else {
+            a.to_lowercase() == b.to_lowercase()
+        }
+    }
+
+    // Whether the name or one of the aliases of the command is `name`.
+    pub(crate) fn matches(&self, info: &CommandInfo, name: &str) -> bool {
+        std::iter::once(&info.name)
+            .chain(&info.aliases)
+            .any(|alias| self.name_eq(alias, name))
+    }
+
+    fn strip<'a>(&self, text: &'a str, prefix: &str) -> Option<&'a str> {
+        let head = text.get(..prefix.len())?;
+
+        if self.name_eq(head, prefix) {
+            Some(&text[prefix.len()..])
+        } else {
+            None
+        }
+    }
+
+    // Strip the nickname at the start of the text, return `None` if it's not followed by a command.
+    pub(crate) fn strip_nickname<'a>(&self, text: &'a str) -> Option<&'a str> {
+        let text = text.trim_start();
+
+        self.nicknames.iter().find_map(|nickname| {
+            let content = self.strip(text, nickname)?.trim_start_matches(|c: char| {
+                c.is_whitespace() || matches!(c, ',' | ':' | '，' | '：')
+            });
+
+            if self.is_command(content) {
+                Some(content)
+            } else {
+                None
+            }
+        })
+    }
+
+    pub(crate) fn is_command(&self, text: &str) -> bool {
+        self.parse(text).is_some()
+    }
+
+    // The name is lowercased when the commands are case insensitive,
+    // and the content keeps the raw text after the name.
+    pub(crate) fn parse<'a>(&'a self, text: &'a str) -> Option<CommandMatch<'a>> {
+        let text = text.trim_start();
+
+        let prefix = self
+            .prefixes
+            .iter()
+            .filter(|prefix| self.strip(text, prefix).is_some())
+            .max_by_key(|prefix| prefix.len())?;
+
+        self.strip(text, prefix).map(|rest| {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+
+            let name = if self.case_sensitive {
+                rest[..end].to_string()
+            } else {
+                rest[..end].to_lowercase()
+            };
+
+            CommandMatch {
+                prefix,
+                name,
+                content: rest[end..].trim_start(),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommandConfig, CommandMatch};
+
+    #[test]
+    fn check_command_config() {
+        let config = CommandConfig::new()
+            .prefixes(&["!", "#", "!!"])
+            .nickname("Wood")
+            .case_sensitive(false)
+            .group_require_at(1, false);
+
+        assert_eq!(
+            config.parse("  !!Ban @member 1h"),
+            Some(CommandMatch {
+                prefix: "!!",
+                name: "ban".to_string(),
+                content: "@member 1h",
+            })
+        );
+        assert_eq!(config.parse("#").unwrap().name, "");
+        assert_eq!(config.parse("/help"), None);
+        assert_eq!(config.prefix(), "!");
+
+        assert_eq!(config.strip_nickname("wood, !help"), Some("!help"));
+        assert_eq!(config.strip_nickname("wood!help"), Some("!help"));
+        assert_eq!(config.strip_nickname("WOOD"), None);
+        assert_eq!(config.strip_nickname("woodpecker"), None);
+        assert_eq!(config.strip_nickname("wood is nice"), None);
+
+        assert!(!config.is_require_at(1));
+        assert!(config.is_require_at(2));
+
+        let config = CommandConfig::default();
+        assert_eq!(config.parse("/Help").unwrap().name, "Help");
+        assert_eq!(config.strip_nickname("wood /help"), None);
+
+        // no prefix is left, so the default one is kept
+        let config = CommandConfig::new().prefixes(&[]);
+        assert_eq!(config.parse("/help").unwrap().name, "help");
+        let config = CommandConfig::new().prefixes(&["", ""]);
+        assert_eq!(config.prefix(), "/");
+    }
+}
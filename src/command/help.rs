@@ -0,0 +1,263 @@
+use super::{CommandConfig, CommandInfo};
+
+// `path` is the full name of the command, e.g. `todo add`.
+fn command_line(prefix: &str, path: &str, info: &CommandInfo) -> String {
+    if info.usage.is_empty() {
+        format!("{}{}", prefix, path)
+    } else {
+        format!("{}{} {}", prefix, path, info.usage)
+    }
+}
+
+fn command_list(prefix: &str, parent: &str, commands: &[CommandInfo]) -> Vec<String> {
+    commands
+        .iter()
+        .map(|info| {
+            let path = if parent.is_empty() {
+                info.name.clone()
+            } else {
+                format!("{} {}", parent, info.name)
+            };
+
+            if info.desc.is_empty() {
+                format!("{}{}", prefix, path)
+            } else {
+                format!("{}{} - {}", prefix, path, info.desc)
+            }
+        })
+        .collect()
+}
+
+// The details of a command, with its subcommands if there are any.
+pub(crate) fn command_help(prefix: &str, path: &str, info: &CommandInfo) -> String {
+    let mut lines = vec![command_line(prefix, path, info)];
+
+    if !info.desc.is_empty() {
+        lines.push(info.desc.clone());
+    }
+    if !info.aliases.is_empty() {
+        let parent = path.rsplit_once(' ').map_or("", |(parent, _)| parent);
+        let aliases: Vec<String> = info
+            .aliases
+            .iter()
+            .map(|alias| format!("{}{}", prefix, [parent, alias].join(" ").trim_start()))
+            .collect();
+        lines.push(format!("Aliases: {}", aliases.join(", ")));
+    }
+    if !info.subcommands.is_empty() {
+        lines.push("Subcommands:".to_string());
+        lines.extend(command_list(prefix, path, &info.subcommands));
+    }
+
+    lines.join("\n")
+}
+
+// Tell the user the most similar command, `parent` is empty for the top level commands.
+pub(crate) fn unknown_command(
+    prefix: &str,
+    parent: &str,
+    name: &str,
+    commands: &[CommandInfo],
+) -> String {
+    let names = commands
+        .iter()
+        .flat_map(|info| std::iter::once(&info.name).chain(&info.aliases));
+
+    match (parent, suggest(name, names)) {
+        ("", Some(similar)) => format!(
+            "Unknown command `{}`, did you mean `{}{}`?",
+            name, prefix, similar
+        ),
+        ("", None) => format!(
+            "Unknown command `{}`, send `{}help` to see all the commands.",
+            name, prefix
+        ),
+        (_, Some(similar)) => format!(
+            "Unknown subcommand `{}` of `{}{}`, did you mean `{}{} {}`?",
+            name, prefix, parent, prefix, parent, similar
+        ),
+        (_, None) => format!(
+            "Unknown subcommand `{}` of `{}{}`, send `{}help {}` to see all the subcommands.",
+            name, prefix, parent, prefix, parent
+        ),
+    }
+}
+
+// The reply of `/help`, or `/help <name>...` for a single command or subcommand,
+// which is shown with the first prefix in the config.
+pub(crate) fn help_text(
+    commands: &[CommandInfo],
+    config: &CommandConfig,
+    names: &[String],
+) -> String {
+    let prefix = config.prefix();
+
+    if names.is_empty() {
+        if commands.is_empty() {
+            return "No command is available.".to_string();
+        }
+
+        let mut lines = vec!["Commands:".to_string()];
+        lines.extend(command_list(prefix, "", commands));
+        lines.push(format!("Send `{}help <command>` for more details.", prefix));
+
+        return lines.join("\n");
+    }
+
+    let mut path: Vec<&str> = vec![];
+    let mut found = None;
+    let mut candidates = commands;
+
+    for (i, name) in names.iter().enumerate() {
+        // `/help /ban` is the same as `/help ban`
+        let name = match config.parse(name) {
+            Some(command) if i == 0 => command.name,
+            _ => name.clone(),
+        };
+
+        match candidates.iter().find(|info| config.matches(info, &name)) {
+            Some(info) => {
+                path.push(&info.name);
+                found = Some(info);
+                candidates = &info.subcommands;
+            }
+            None => return unknown_command(prefix, &path.join(" "), &name, candidates),
+        }
+    }
+
+    found.map_or_else(String::new, |info| {
+        command_help(prefix, &path.join(" "), info)
+    })
+}
+
+// The name within an edit distance of 2, or starting with `name`.
+fn suggest<'a>(name: &str, candidates: impl Iterator<Item = &'a String>) -> Option<&'a str> {
+    let name = name.to_lowercase();
+
+    candidates
+        .map(|candidate| {
+            let candidate_lowercase = candidate.to_lowercase();
+            let distance = if !name.is_empty() && candidate_lowercase.starts_with(&name) {
+                0
+            } else {
+                edit_distance(&name, &candidate_lowercase)
+            };
+
+            (distance, candidate)
+        })
+        .filter(|(distance, _)| *distance <= 2 && *distance < name.chars().count())
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.as_str())
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                previous
+            } else {
+                previous.min(row[j]).min(current) + 1
+            };
+            previous = current;
+        }
+    }
+
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{edit_distance, help_text};
+    use crate::command::{CommandConfig, CommandInfo};
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn check_help_text() {
+        let commands = vec![
+            CommandInfo {
+                aliases: vec!["b".to_string()],
+                desc: "Ban a member.".to_string(),
+                usage: "<@member> <duration>".to_string(),
+                ..CommandInfo::new("ban")
+            },
+            CommandInfo::new("hi"),
+        ];
+        let config = CommandConfig::default();
+
+        assert_eq!(
+            help_text(&commands, &config, &[]),
+            "Commands:\n/ban - Ban a member.\n/hi\nSend `/help <command>` for more details."
+        );
+        assert_eq!(
+            help_text(&commands, &config, &names(&["b"])),
+            "/ban <@member> <duration>\nBan a member.\nAliases: /b"
+        );
+        assert_eq!(
+            help_text(&commands, &config, &names(&["kick"])),
+            "Unknown command `kick`, send `/help` to see all the commands."
+        );
+        assert_eq!(
+            help_text(&commands, &config, &names(&["bam"])),
+            "Unknown command `bam`, did you mean `/ban`?"
+        );
+
+        let config = CommandConfig::new().prefixes(&["!"]).case_sensitive(false);
+        assert_eq!(
+            help_text(&commands, &config, &names(&["!B"])),
+            "!ban <@member> <duration>\nBan a member.\nAliases: !b"
+        );
+    }
+
+    #[test]
+    fn check_subcommand_help_text() {
+        let commands = vec![CommandInfo {
+            desc: "Manage the todo list.".to_string(),
+            subcommands: vec![
+                CommandInfo {
+                    aliases: vec!["a".to_string()],
+                    desc: "Add a todo.".to_string(),
+                    usage: "<text>".to_string(),
+                    ..CommandInfo::new("add")
+                },
+                CommandInfo::new("list"),
+            ],
+            ..CommandInfo::new("todo")
+        }];
+        let config = CommandConfig::default();
+
+        assert_eq!(
+            help_text(&commands, &config, &names(&["todo"])),
+            "/todo\nManage the todo list.\nSubcommands:\n/todo add - Add a todo.\n/todo list"
+        );
+        assert_eq!(
+            help_text(&commands, &config, &names(&["todo", "a"])),
+            "/todo add <text>\nAdd a todo.\nAliases: /todo a"
+        );
+        assert_eq!(
+            help_text(&commands, &config, &names(&["todo", "lsit"])),
+            "Unknown subcommand `lsit` of `/todo`, did you mean `/todo list`?"
+        );
+        assert_eq!(
+            help_text(&commands, &config, &names(&["todo", "x"])),
+            "Unknown subcommand `x` of `/todo`, send `/help todo` to see all the subcommands."
+        );
+    }
+
+    #[test]
+    fn check_edit_distance() {
+        assert_eq!(edit_distance("add", "add"), 0);
+        assert_eq!(edit_distance("ad", "add"), 1);
+        assert_eq!(edit_distance("lsit", "list"), 2);
+        assert_eq!(edit_distance("", "done"), 4);
+    }
+}
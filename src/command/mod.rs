@@ -0,0 +1,262 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use crate::event_listener::EventHandler;
+use crate::message::MessageChain;
+use crate::{BoxFuture, Context, Middleware, Next, Result};
+
+mod args;
+pub use args::{Arg, Args, At, FromArg, FromArgs, Image, Param, UsageError};
+
+mod config;
+pub use config::CommandConfig;
+
+mod help;
+pub(crate) use help::help_text;
+use help::{command_help, unknown_command};
+
+// The metadata of a command, used by `/help`.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct CommandInfo {
+    pub name: String,
+    pub aliases: Vec<String>,
+    pub desc: String,
+    pub usage: String,
+    pub subcommands: Vec<CommandInfo>,
+}
+
+impl CommandInfo {
+    pub fn new(name: &str) -> Self {
+        CommandInfo {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+// A command with its handler, registered by `bot.register(command)`.
+// It's usually generated by `#[wood::command(...)]`.
+pub struct Command {
+    info: CommandInfo,
+    handler: Option<Box<EventHandler>>,
+    subcommands: Vec<Command>,
+    middlewares: Vec<Arc<dyn Middleware>>,
+    priority: i32,
+}
+
+impl Command {
+    pub fn new<F, Fut>(name: &str, handler: F) -> Self
+    where
+        F: Fn(Context) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        Command {
+            info: CommandInfo::new(name),
+            handler: Some(Box::new(move |ctx| Box::pin(handler(ctx)))),
+            subcommands: vec![],
+            middlewares: vec![],
+            priority: 0,
+        }
+    }
+
+    // The arguments are parsed before calling the handler,
+    // and the usage is replied if they are invalid.
+    pub fn with_args<T, F, Fut>(name: &str, handler: F) -> Self
+    where
+        T: FromArgs + 'static,
+        F: Fn(Context, T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        Command {
+            info: CommandInfo {
+                usage: T::usage(),
+                ..CommandInfo::new(name)
+            },
+            handler: Some(Box::new(move |ctx| match ctx.parse_args::<T>() {
+                Ok(args) => Box::pin(handler(ctx, args)),
+                Err(e) => Box::pin(async move {
+                    ctx.reply(MessageChain::from(e.to_string())).await?;
+                    Ok(())
+                }),
+            })),
+            subcommands: vec![],
+            middlewares: vec![],
+            priority: 0,
+        }
+    }
+
+    // A command which only holds subcommands, e.g. `/todo` in `/todo add milk`.
+    // The subcommands are listed if none of them is given.
+    pub fn group(name: &str) -> Self {
+        Command {
+            info: CommandInfo::new(name),
+            handler: None,
+            subcommands: vec![],
+            middlewares: vec![],
+            priority: 0,
+        }
+    }
+
+    // The subcommand is matched by the first argument, and the rest are its arguments.
+    // Otherwise the handler of this command is called if there is one,
+    // or a similar subcommand is suggested.
+    pub fn subcommand(mut self, command: Command) -> Self {
+        self.info.subcommands.push(command.info.clone());
+        self.subcommands.push(command);
+        self
+    }
+
+    // Middleware only for this command and its subcommands,
+    // which run after the ones added by `bot.use_middleware`.
+    pub fn use_middleware<M>(mut self, middleware: M) -> Self
+    where
+        M: Middleware + 'static,
+    {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    pub fn aliases(mut self, aliases: &[&str]) -> Self {
+        self.info.aliases = aliases.iter().map(|alias| alias.to_string()).collect();
+        self
+    }
+
+    pub fn desc(mut self, desc: &str) -> Self {
+        self.info.desc = desc.to_string();
+        self
+    }
+
+    pub fn usage(mut self, usage: &str) -> Self {
+        self.info.usage = usage.to_string();
+        self
+    }
+
+    // Like `bot.on_with_priority`, the ones with higher priority are called first.
+    // It's ignored for subcommands, which are called by their parent.
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn info(&self) -> &CommandInfo {
+        &self.info
+    }
+
+    // Return the info, the handler and the priority.
+    pub(crate) fn into_parts(self) -> (CommandInfo, Box<EventHandler>, i32) {
+        let Command {
+            info,
+            handler,
+            subcommands,
+            middlewares,
+            priority,
+        } = self;
+
+        if subcommands.is_empty() {
+            let handler = handler.unwrap_or_else(|| Box::new(|_| Box::pin(async { Ok(()) })));
+            return (info, with_middlewares(handler, middlewares), priority);
+        }
+
+        let subcommands: Vec<(CommandInfo, Box<EventHandler>)> = subcommands
+            .into_iter()
+            .map(|command| {
+                let (info, handler, _) = command.into_parts();
+                (info, handler)
+            })
+            .collect();
+        let group = info.clone();
+
+        let dispatch: Box<EventHandler> = Box::new(move |ctx| {
+            let word = ctx.first_word().map(|word| word.to_string());
+
+            if let Some(word) = &word {
+                let config = ctx.command_config();
+
+                if let Some((info, handler)) = subcommands
+                    .iter()
+                    .find(|(info, _)| config.matches(info, word))
+                {
+                    return handler(ctx.enter_subcommand(&info.name));
+                }
+            }
+
+            if let Some(handler) = &handler {
+                return handler(ctx);
+            }
+
+            let prefix = ctx.command_prefix().to_string();
+            let path = ctx.command_name().to_string();
+            let text = match word {
+                Some(word) => unknown_command(&prefix, &path, &word, &group.subcommands),
+                None => command_help(&prefix, &path, &group),
+            };
+
+            Box::pin(async move {
+                ctx.reply(MessageChain::from(text)).await?;
+                Ok(())
+            })
+        });
+
+        (info, with_middlewares(dispatch, middlewares), priority)
+    }
+}
+
+fn with_middlewares(
+    handler: Box<EventHandler>,
+    middlewares: Vec<Arc<dyn Middleware>>,
+) -> Box<EventHandler> {
+    if middlewares.is_empty() {
+        return handler;
+    }
+
+    let handler: Arc<EventHandler> = handler.into();
+    let middlewares: Arc<[Arc<dyn Middleware>]> = middlewares.into();
+
+    Box::new(move |ctx| {
+        let handler = handler.clone();
+        let middlewares = middlewares.clone();
+
+        Box::pin(async move {
+            let endpoint = |ctx| handler(ctx) as BoxFuture;
+            Next::new(&middlewares, &endpoint).run(ctx).await
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{At, CommandInfo};
+    use crate::{Context, Result};
+    use std::time::Duration;
+
+    #[crate::command(name = "ban", aliases = ["b"], desc = "Ban a member.")]
+    async fn ban(_ctx: Context, _args: (At, Duration)) -> Result<()> {
+        Ok(())
+    }
+
+    #[crate::command]
+    async fn hi(_ctx: Context) -> Result<()> {
+        Ok(())
+    }
+
+    #[crate::command(name = "greet", subcommands = [hi])]
+    async fn greet(_ctx: Context) -> Result<()> {
+        Ok(())
+    }
+
+    #[test]
+    fn check_command_macro() {
+        assert_eq!(
+            ban().info(),
+            &CommandInfo {
+                name: "ban".to_string(),
+                aliases: vec!["b".to_string()],
+                desc: "Ban a member.".to_string(),
+                usage: "<@member> <duration>".to_string(),
+                subcommands: vec![],
+            }
+        );
+        assert_eq!(hi().info(), &CommandInfo::new("hi"));
+        assert_eq!(greet().info().subcommands, vec![CommandInfo::new("hi")]);
+    }
+}
@@ -1,16 +1,30 @@
-use regex::Regex;
-
+use serde_json::Value;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::announcement::{Announcement, PublishOptions};
+use crate::command::{Args, CommandConfig, FromArgs, UsageError};
 use crate::error::Error;
+use crate::event::{Event, RequestOperation, SubjectKind};
+use crate::event_listener::EventType;
+use crate::files::GroupFiles;
+use crate::message::media::{ImageId, MediaSource, VoiceId};
+use crate::message::sender::{Group, GroupSender};
 use crate::message::{ChatroomType, MessageChain, Sender, SingleMessage};
 use crate::{Bot, Result};
 
 #[allow(dead_code)]
 pub struct Context {
     bot: Bot,
+    event: Event,
 
     is_at_me: bool,
 
     is_command: bool,
+    command_prefix: String,
     command_name: String,
 
     chatroom_type: ChatroomType,
@@ -22,17 +36,76 @@ pub struct Context {
 
     message_id: i64,
     message_chain: MessageChain,
+
+    // Only exists when the event is posted by the webhook with `reply` enabled.
+    webhook_reply: Option<WebhookReply>,
+
+    // Values added by the middleware, which are shared by the clones.
+    extensions: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+
+    // Shared by the clones passed to the listeners of the same event.
+    propagation_stopped: Arc<AtomicBool>,
+}
+
+pub(crate) type WebhookReply = Arc<Mutex<ReplySlot>>;
+
+// The reply returned in the response body of the webhook,
+// which is only used when the handlers reply once.
+#[derive(Default)]
+pub(crate) enum ReplySlot {
+    #[default]
+    Empty,
+    // the command for the response, and the message to send by the api if another reply comes
+    Held(Value, MessageChain, Option<i64>),
+    // the replies are sent by the api, or the request is already responded
+    Closed,
+}
+
+impl ReplySlot {
+    // The command returned to mirai, and the later replies are sent by the api.
+    pub(crate) fn take(&mut self) -> Option<Value> {
+        match std::mem::replace(self, ReplySlot::Closed) {
+            ReplySlot::Held(command, _, _) => Some(command),
+            _ => None,
+        }
+    }
 }
 
 impl Context {
-    pub fn new<S>(bot: Bot, sender: S, mut message_chain: MessageChain) -> Result<Self>
+    pub fn new(bot: Bot, event: Event) -> Result<Self> {
+        let ctx = match &event {
+            Event::FriendMessage {
+                sender,
+                message_chain,
+            } => Context::from_message(bot, sender, message_chain.clone())?,
+
+            Event::GroupMessage {
+                sender,
+                message_chain,
+            } => Context::from_message(bot, sender, message_chain.clone())?,
+
+            Event::TempMessage {
+                sender,
+                message_chain,
+            } => Context::from_message(bot, sender, message_chain.clone())?,
+
+            Event::StrangerMessage {
+                sender,
+                message_chain,
+            } => Context::from_message(bot, sender, message_chain.clone())?,
+
+            _ => Context::from_event(bot, &event),
+        };
+
+        Ok(Context { event, ..ctx })
+    }
+
+    fn from_message<S>(bot: Bot, sender: &S, mut message_chain: MessageChain) -> Result<Self>
     where
         S: Sender,
     {
-        let source_message = message_chain[0].clone();
-
-        let message_id = match source_message {
-            SingleMessage::Source { id, .. } => id,
+        let message_id = match message_chain.first() {
+            Some(SingleMessage::Source { id, .. }) => *id,
             _ => {
                 return Err(Error::new(
                     "[Error] Receiving error message type when creating context.",
@@ -43,71 +116,73 @@ impl Context {
         // remove the source message
         message_chain.remove(0);
 
-        let chatroom_type = sender.chatroom_type();
-
-        let is_at_me = match chatroom_type {
-            ChatroomType::Friend => false,
-            ChatroomType::Group => match message_chain[0] {
-                SingleMessage::At { target, .. } => target == bot.qq(),
-                _ => false,
-            },
-        };
-
-        // remove the at message
-        if is_at_me {
+        // remove the quote message, which can be got by `quoted_message`
+        if let Some(SingleMessage::Quote { .. }) = message_chain.first() {
             message_chain.remove(0);
         }
 
-        let is_command = match chatroom_type {
-            ChatroomType::Friend => match &message_chain[0] {
-                SingleMessage::Plain { text } => text.as_str().trim().starts_with("/"),
-                _ => false,
-            },
-            ChatroomType::Group => {
-                is_at_me
-                    // the second `SingleMessage` will be the content
-                    && match &message_chain[0] {
-                        SingleMessage::Plain { text } => text.as_str().trim().starts_with("/"),
-                        _ => false,
-                    }
-            }
-        };
-
-        let mut command_name = "";
-        let mut command_attrs = vec![];
+        let chatroom_type = sender.chatroom_type();
+        let config = bot.command_config();
 
-        if is_command {
-            let command_pattern = Regex::new(r"^\s*/(\S+)").unwrap();
+        let mut is_at_me = false;
 
-            // the first message of command must be `SingleMessage::Plain`
-            if let SingleMessage::Plain { text } = &message_chain[0] {
-                if let Some(caps) = command_pattern.captures(text) {
-                    command_name = caps.get(1).map_or("", |m| m.as_str());
+        if chatroom_type == ChatroomType::Group {
+            if let Some(SingleMessage::At { target, .. }) = message_chain.first() {
+                is_at_me = *target == bot.qq();
+            }
 
-                    let mut split = text.trim().split(" ");
-                    split.next();
-                    for attr in split {
-                        command_attrs.push(attr)
-                    }
+            // remove the at message
+            if is_at_me {
+                message_chain.remove(0);
+            } else if let Some(SingleMessage::Plain { text }) = message_chain.first_mut() {
+                // a nickname followed by a command is treated as an at
+                if let Some(content) = config.strip_nickname(text) {
+                    is_at_me = true;
+                    *text = content.to_string();
                 }
             }
         }
 
-        let mut content_message_chain = vec![];
+        // the command must be started by a `SingleMessage::Plain`,
+        // and an at is required in groups unless it's disabled in the config
+        let require_at =
+            chatroom_type == ChatroomType::Group && config.is_require_at(sender.chatroom_id());
+
+        let command = match message_chain.first() {
+            Some(SingleMessage::Plain { text }) if is_at_me || !require_at => {
+                config.parse(text).map(|command| {
+                    (
+                        command.prefix.to_string(),
+                        command.name,
+                        // keep the content as it is, which is parsed by `args`
+                        command.content.to_string(),
+                    )
+                })
+            }
+            _ => None,
+        };
 
-        content_message_chain.push(SingleMessage::Plain {
-            text: command_attrs.join(" "),
-        });
+        let is_command = command.is_some();
+        let (command_prefix, command_name, command_content) = command.unwrap_or_default();
 
-        content_message_chain.extend_from_slice(&message_chain[1..]);
+        // the command name is removed from the content of a command
+        let content_message_chain = if is_command {
+            let mut content_message_chain = MessageChain::new().text(command_content);
+            content_message_chain.extend(message_chain.into_iter().skip(1));
+            content_message_chain
+        } else {
+            message_chain
+        };
 
         Ok(Context {
             bot,
+            event: Event::Unknown(Value::Null),
 
             is_at_me,
 
             is_command,
-            command_name: command_name.to_string(),
+            command_prefix,
+            command_name,
 
             chatroom_type,
             chatroom_id: sender.chatroom_id(),
@@ -118,16 +193,175 @@ impl Context {
 
             message_id,
             message_chain: content_message_chain,
+
+            webhook_reply: None,
+
+            extensions: HashMap::new(),
+
+            propagation_stopped: Default::default(),
         })
     }
 
-    pub fn clone(&self) -> Self {
+    // Events which are not happening in a chatroom,
+    // e.g. `BotOnlineEvent`, have a `chatroom_id` of 0 and can't be replied.
+    fn from_event(bot: Bot, event: &Event) -> Self {
+        let no_chatroom = (ChatroomType::Friend, 0, "".to_string());
+        let friend = |id: i64| (ChatroomType::Friend, id, "".to_string());
+        let group = |group: &Group| (ChatroomType::Group, group.id, group.name.clone());
+        let chatroom_of = |sender: &dyn Sender| {
+            (
+                sender.chatroom_type(),
+                sender.chatroom_id(),
+                sender.chatroom_name(),
+            )
+        };
+        let sender_of = |sender: &dyn Sender| (sender.sender_id(), sender.sender_nickname());
+        let operator_of = |operator: &Option<GroupSender>| match operator {
+            Some(operator) => sender_of(operator),
+            None => (0, "".to_string()),
+        };
+        let bot_itself = (bot.qq(), "".to_string());
+
+        let (chatroom, sender) = match event {
+            Event::FriendSyncMessage { subject, .. } => (chatroom_of(subject), bot_itself),
+            Event::TempSyncMessage { subject, .. } => (chatroom_of(subject), bot_itself),
+            Event::StrangerSyncMessage { subject, .. } => (chatroom_of(subject), bot_itself),
+            Event::GroupSyncMessage { subject, .. } => (group(subject), bot_itself),
+
+            Event::FriendInputStatusChangedEvent { friend, .. }
+            | Event::FriendNickChangedEvent { friend, .. }
+            | Event::FriendAddEvent { friend, .. }
+            | Event::FriendDeleteEvent { friend } => (chatroom_of(friend), sender_of(friend)),
+            Event::FriendRecallEvent {
+                author_id,
+                operator,
+                ..
+            } => (friend(*operator), (*author_id, "".to_string())),
+            Event::NudgeEvent {
+                from_id, subject, ..
+            } => {
+                let chatroom = match subject.kind {
+                    SubjectKind::Group => (ChatroomType::Group, subject.id, "".to_string()),
+                    SubjectKind::Friend => friend(subject.id),
+                    SubjectKind::Stranger => (ChatroomType::Stranger, subject.id, "".to_string()),
+                };
+                (chatroom, (*from_id, "".to_string()))
+            }
+
+            Event::BotGroupPermissionChangeEvent { group: g, .. }
+            | Event::BotLeaveEventActive { group: g }
+            | Event::GroupAllowConfessTalkEvent { group: g, .. } => (group(g), (0, "".to_string())),
+            Event::BotMuteEvent { operator, .. } | Event::BotUnmuteEvent { operator } => {
+                (chatroom_of(operator), sender_of(operator))
+            }
+            Event::BotJoinGroupEvent {
+                group: g,
+                invitor: operator,
+            }
+            | Event::BotLeaveEventKick { group: g, operator }
+            | Event::BotLeaveEventDisband { group: g, operator }
+            | Event::GroupNameChangeEvent {
+                group: g, operator, ..
+            }
+            | Event::GroupEntranceAnnouncementChangeEvent {
+                group: g, operator, ..
+            }
+            | Event::GroupMuteAllEvent {
+                group: g, operator, ..
+            }
+            | Event::GroupAllowAnonymousChatEvent {
+                group: g, operator, ..
+            }
+            | Event::GroupAllowMemberInviteEvent {
+                group: g, operator, ..
+            } => (group(g), operator_of(operator)),
+            Event::GroupRecallEvent {
+                author_id,
+                group: g,
+                ..
+            } => (group(g), (*author_id, "".to_string())),
+
+            Event::MemberJoinEvent { member, .. }
+            | Event::MemberLeaveEventKick { member, .. }
+            | Event::MemberLeaveEventQuit { member }
+            | Event::MemberCardChangeEvent { member, .. }
+            | Event::MemberSpecialTitleChangeEvent { member, .. }
+            | Event::MemberPermissionChangeEvent { member, .. }
+            | Event::MemberMuteEvent { member, .. }
+            | Event::MemberUnmuteEvent { member, .. }
+            | Event::MemberHonorChangeEvent { member, .. } => {
+                (chatroom_of(member), sender_of(member))
+            }
+
+            Event::NewFriendRequestEvent { from_id, nick, .. }
+            | Event::BotInvitedJoinGroupRequestEvent { from_id, nick, .. } => {
+                (friend(*from_id), (*from_id, nick.clone()))
+            }
+            Event::MemberJoinRequestEvent {
+                from_id,
+                group_id,
+                group_name,
+                nick,
+                ..
+            } => (
+                (ChatroomType::Group, *group_id, group_name.clone()),
+                (*from_id, nick.clone()),
+            ),
+
+            Event::CommandExecutedEvent {
+                friend: Some(friend),
+                ..
+            } => (chatroom_of(friend), sender_of(friend)),
+            Event::CommandExecutedEvent {
+                member: Some(member),
+                ..
+            } => (chatroom_of(member), sender_of(member)),
+
+            _ => (no_chatroom, (0, "".to_string())),
+        };
+
+        let (chatroom_type, chatroom_id, chatroom_name) = chatroom;
+        let (sender_id, sender_nickname) = sender;
+
+        Context {
+            bot,
+            event: event.clone(),
+
+            is_at_me: false,
+
+            is_command: false,
+            command_prefix: "".to_string(),
+            command_name: "".to_string(),
+
+            chatroom_type,
+            chatroom_id,
+            chatroom_name,
+
+            sender_id,
+            sender_nickname,
+
+            message_id: 0,
+            message_chain: event.message_chain().cloned().unwrap_or_default(),
+
+            webhook_reply: None,
+
+            extensions: HashMap::new(),
+
+            propagation_stopped: Default::default(),
+        }
+    }
+}
+
+impl Clone for Context {
+    fn clone(&self) -> Self {
         Context {
             bot: self.bot.clone(),
+            event: self.event.clone(),
 
             is_at_me: self.is_at_me,
 
             is_command: self.is_command,
+            command_prefix: self.command_prefix.clone(),
             command_name: self.command_name.clone(),
 
             chatroom_type: self.chatroom_type.clone(),
@@ -139,8 +373,25 @@ impl Context {
 
             message_id: self.message_id,
             message_chain: self.message_chain.clone(),
+
+            webhook_reply: self.webhook_reply.clone(),
+
+            extensions: self.extensions.clone(),
+
+            propagation_stopped: self.propagation_stopped.clone(),
         }
     }
+}
+
+impl Context {
+    pub fn event(&self) -> &Event {
+        &self.event
+    }
+
+    // Friend, group, temp and stranger messages.
+    pub fn is_message(&self) -> bool {
+        self.event.is_message()
+    }
 
     pub fn chatroom_type(&self) -> ChatroomType {
         self.chatroom_type.clone()
@@ -162,17 +413,65 @@ impl Context {
         self.sender_nickname.clone()
     }
 
+    // The id of the received message, which is 0 if the event is not a message.
+    pub fn message_id(&self) -> i64 {
+        self.message_id
+    }
+
+    // The one who is nudged, 0 if the event is not a `NudgeEvent`.
+    // The one who nudges is the sender.
+    pub fn nudge_target(&self) -> i64 {
+        match &self.event {
+            Event::NudgeEvent { target, .. } => *target,
+            _ => 0,
+        }
+    }
+
+    pub fn is_nudging_me(&self) -> bool {
+        self.nudge_target() == self.bot.qq()
+    }
+
     pub fn message_chain(&self) -> MessageChain {
         self.message_chain.clone()
     }
 
+    // Used by the middleware to change the content before the handlers.
+    pub fn set_message_chain(&mut self, message_chain: MessageChain) {
+        self.message_chain = message_chain;
+    }
+
+    // Share a value with the following middleware and handlers,
+    // e.g. the permission checked by an auth middleware.
+    pub fn insert_extension<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.extensions.insert(TypeId::of::<T>(), Arc::new(value));
+    }
+
+    // The listeners after this one won't be called for the event.
+    pub fn stop_propagation(&self) {
+        self.propagation_stopped.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_propagation_stopped(&self) -> bool {
+        self.propagation_stopped.load(Ordering::SeqCst)
+    }
+
+    // The state added by `bot.manage(state)`.
+    pub fn state<T: Send + Sync + 'static>(&self) -> Result<&T> {
+        self.bot.state()
+    }
+
+    pub fn extension<T: 'static>(&self) -> Option<&T> {
+        self.extensions
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+
     pub fn is_at_message(&self) -> bool {
         match self.chatroom_type {
-            ChatroomType::Friend => false,
-            ChatroomType::Group => match self.message_chain[0] {
-                SingleMessage::At { .. } => true,
-                _ => false,
-            },
+            ChatroomType::Group => {
+                matches!(self.message_chain.first(), Some(SingleMessage::At { .. }))
+            }
+            _ => false,
         }
     }
 
@@ -197,29 +496,387 @@ impl Context {
         &self.command_name
     }
 
-    pub async fn reply(&self, message_chain: MessageChain) -> Result<()> {
+    // The prefix which starts the command, e.g. `/` in `/help`.
+    pub fn command_prefix(&self) -> &str {
+        &self.command_prefix
+    }
+
+    // The arguments of the command, quoted strings, flags, mentions and images are supported.
+    pub fn args(&self) -> std::result::Result<Args, UsageError> {
+        Args::parse_with_prefix(
+            &self.command_prefix,
+            &self.command_name,
+            &self.message_chain,
+        )
+    }
+
+    // Parse the positional arguments into a tuple, e.g. `let (at, duration): (At, Duration)`.
+    // The error tells the usage of the command, which can be replied to the user directly.
+    pub fn parse_args<T: FromArgs>(&self) -> std::result::Result<T, UsageError> {
+        self.args()?.parse_into()
+    }
+
+    pub(crate) fn command_config(&self) -> &CommandConfig {
+        self.bot.command_config()
+    }
+
+    // The first word of the content, which may be the name of a subcommand.
+    pub(crate) fn first_word(&self) -> Option<&str> {
+        match self.message_chain.first() {
+            Some(SingleMessage::Plain { text }) => text.split_whitespace().next(),
+            _ => None,
+        }
+    }
+
+    // The context of the subcommand named by the first word, e.g. `add` in `/todo add milk`,
+    // where the command name becomes `todo add` and the content becomes `milk`.
+    pub(crate) fn enter_subcommand(&self, name: &str) -> Context {
+        let mut ctx = self.clone();
+        ctx.command_name = format!("{} {}", self.command_name, name);
+
+        if let Some(SingleMessage::Plain { text }) = ctx.message_chain.first_mut() {
+            let content = text.trim_start();
+            let end = content.find(char::is_whitespace).unwrap_or(content.len());
+            *text = content[end..].trim_start().to_string();
+        }
+
+        ctx
+    }
+
+    pub(crate) fn set_webhook_reply(&mut self, webhook_reply: WebhookReply) {
+        self.webhook_reply = Some(webhook_reply);
+    }
+
+    // The friend or the group where the message is, used by `recall` and `messageFromId`.
+    fn message_target(&self) -> i64 {
+        match self.chatroom_type {
+            ChatroomType::Group => self.chatroom_id,
+            _ => self.sender_id,
+        }
+    }
+
+    // Return the id of the sent message,
+    // which is `None` when the message is returned to the webhook.
+    async fn send(&self, message_chain: MessageChain, quote: Option<i64>) -> Result<Option<i64>> {
+        if self.chatroom_id == 0 {
+            return Err(Error::new(&format!(
+                "[Error] The event `{}` can't be replied.",
+                EventType::from(&self.event)
+            )));
+        }
+
+        // temp messages are sent to the member through the group
+        let (target, group) = match self.chatroom_type {
+            ChatroomType::Temp => (self.sender_id, Some(self.chatroom_id)),
+            _ => (self.chatroom_id, None),
+        };
+
+        // The first reply is held for the webhook response. Once another one comes,
+        // both are sent by the api in order, so is everything after the response.
+        let held = match &self.webhook_reply {
+            Some(webhook_reply) => {
+                let mut slot = webhook_reply.lock().unwrap();
+
+                match std::mem::replace(&mut *slot, ReplySlot::Closed) {
+                    ReplySlot::Empty => {
+                        let command = self.bot.api().send_message_command(
+                            self.chatroom_type.clone(),
+                            target,
+                            group,
+                            message_chain.clone(),
+                            quote,
+                        )?;
+                        *slot = ReplySlot::Held(command, message_chain, quote);
+
+                        return Ok(None);
+                    }
+                    ReplySlot::Held(_, held, held_quote) => Some((held, held_quote)),
+                    ReplySlot::Closed => None,
+                }
+            }
+            None => None,
+        };
+
+        if let Some((held, held_quote)) = held {
+            self.send_by_api(target, group, held, held_quote).await?;
+        }
+
+        self.send_by_api(target, group, message_chain, quote).await
+    }
+
+    async fn send_by_api(
+        &self,
+        target: i64,
+        group: Option<i64>,
+        message_chain: MessageChain,
+        quote: Option<i64>,
+    ) -> Result<Option<i64>> {
+        match group {
+            Some(group) => {
+                self.bot
+                    .send_temp_message(target, group, message_chain, quote)
+                    .await
+            }
+            None => {
+                self.bot
+                    .send_message(self.chatroom_type.clone(), target, message_chain, quote)
+                    .await
+            }
+        }
+    }
+
+    pub async fn reply(&self, message_chain: MessageChain) -> Result<Option<i64>> {
+        self.send(message_chain, None).await
+    }
+
+    pub async fn quote_reply(&self, message_chain: MessageChain) -> Result<Option<i64>> {
+        self.send(message_chain, Some(self.message_id)).await
+    }
+
+    // Recall the received message, the bot must be an administrator in groups.
+    pub async fn recall(&self) -> Result<()> {
+        if self.message_id == 0 {
+            return Err(Error::new(&format!(
+                "[Error] The event `{}` can't be recalled.",
+                EventType::from(&self.event)
+            )));
+        }
+
         self.bot
-            .send_message(
-                self.chatroom_type.clone(),
-                self.chatroom_id,
-                message_chain,
-                None,
-            )
-            .await?;
+            .recall(self.message_target(), self.message_id)
+            .await
+    }
 
-        Ok(())
+    fn group_id(&self) -> Result<i64> {
+        match self.chatroom_type {
+            ChatroomType::Group if self.chatroom_id != 0 => Ok(self.chatroom_id),
+            _ => Err(Error::new(&format!(
+                "[Error] The event `{}` is not happening in a group.",
+                EventType::from(&self.event)
+            ))),
+        }
     }
 
-    pub async fn quote_reply(&self, message_chain: MessageChain) -> Result<()> {
+    // Upload an image which can be sent to the chatroom.
+    pub async fn upload_image<S: Into<MediaSource>>(&self, image: S) -> Result<ImageId> {
         self.bot
-            .send_message(
-                self.chatroom_type.clone(),
-                self.chatroom_id,
-                message_chain,
-                Some(self.message_id),
-            )
-            .await?;
+            .upload_image(self.chatroom_type.clone(), image)
+            .await
+    }
+
+    pub async fn upload_voice<S: Into<MediaSource>>(&self, voice: S) -> Result<VoiceId> {
+        self.bot
+            .upload_voice(self.chatroom_type.clone(), voice)
+            .await
+    }
+
+    // Nudge the sender in the chatroom.
+    pub async fn nudge_sender(&self) -> Result<()> {
+        let (subject, kind) = match self.chatroom_type {
+            ChatroomType::Friend => (self.sender_id, SubjectKind::Friend),
+            ChatroomType::Group => (self.chatroom_id, SubjectKind::Group),
+            ChatroomType::Stranger => (self.sender_id, SubjectKind::Stranger),
+            ChatroomType::Temp => {
+                return Err(Error::new(
+                    "[Error] Nudging in temp chat is not supported by mirai-api-http.",
+                ))
+            }
+        };
+
+        if self.sender_id == 0 {
+            return Err(Error::new(&format!(
+                "[Error] The event `{}` has no sender to nudge.",
+                EventType::from(&self.event)
+            )));
+        }
+
+        self.bot.send_nudge(self.sender_id, subject, kind).await
+    }
+
+    // Publish an announcement in the group where the event happens.
+    pub async fn announce(&self, content: &str, options: PublishOptions) -> Result<Announcement> {
+        self.bot
+            .publish_announcement(self.group_id()?, content, options)
+            .await
+    }
+
+    // The files of the group where the event happens.
+    pub fn group_files(&self) -> Result<GroupFiles> {
+        Ok(self.bot.group_files(self.group_id()?))
+    }
+
+    pub async fn mute_sender(&self, duration: Duration) -> Result<()> {
+        self.bot
+            .api()
+            .mute(self.group_id()?, self.sender_id, duration)
+            .await
+    }
+
+    pub async fn unmute_sender(&self) -> Result<()> {
+        self.bot
+            .api()
+            .unmute(self.group_id()?, self.sender_id)
+            .await
+    }
+
+    // `msg` is the message shown to the kicked sender.
+    pub async fn kick_sender(&self, msg: &str) -> Result<()> {
+        self.bot
+            .api()
+            .kick(self.group_id()?, self.sender_id, msg)
+            .await
+    }
+
+    // Set the received message as an essence message of the group.
+    pub async fn set_essence(&self) -> Result<()> {
+        self.bot
+            .api()
+            .set_essence(self.group_id()?, self.message_id)
+            .await
+    }
+
+    async fn respond_request(&self, operation: RequestOperation, msg: &str) -> Result<()> {
+        self.bot
+            .api()
+            .respond_request(&self.event, operation, msg)
+            .await
+    }
+
+    // Accept a friend request, a member join request or a group invitation.
+    pub async fn accept(&self) -> Result<()> {
+        self.respond_request(RequestOperation::Accept, "").await
+    }
+
+    pub async fn reject(&self, msg: &str, blacklist: bool) -> Result<()> {
+        self.respond_request(RequestOperation::Reject { blacklist }, msg)
+            .await
+    }
+
+    // Only member join requests can be ignored.
+    pub async fn ignore(&self) -> Result<()> {
+        self.respond_request(RequestOperation::Ignore { blacklist: false }, "")
+            .await
+    }
+
+    // The message replied by the received message,
+    // `None` if the received message doesn't quote any message.
+    pub async fn quoted_message(&self) -> Result<Option<Event>> {
+        let quote_id = self.event.message_chain().and_then(|message_chain| {
+            message_chain.iter().find_map(|message| match message {
+                SingleMessage::Quote { id, .. } => Some(*id),
+                _ => None,
+            })
+        });
+
+        match quote_id {
+            Some(id) => Ok(Some(
+                self.bot.message_from_id(self.message_target(), id).await?,
+            )),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Context;
+    use crate::bot::tests::bot;
+    use crate::command::CommandConfig;
+    use crate::event::{Event, Subject, SubjectKind};
+    use crate::message::{ChatroomType, MessageChain, SingleMessage};
+    use serde_json::json;
+
+    #[test]
+    fn check_nudge_context() {
+        let event = Event::NudgeEvent {
+            from_id: 20211114,
+            subject: Subject {
+                id: 20211115,
+                kind: SubjectKind::Group,
+            },
+            action: "戳了戳".to_string(),
+            suffix: "".to_string(),
+            target: 20211113,
+        };
+
+        let ctx = Context::new(bot(), event).unwrap();
+
+        assert_eq!(ctx.chatroom_type(), ChatroomType::Group);
+        assert_eq!(ctx.chatroom_id(), 20211115);
+        assert_eq!(ctx.sender_id(), 20211114);
+        assert_eq!(ctx.nudge_target(), 20211113);
+        assert!(ctx.is_nudging_me());
+    }
+
+    // A group message in group 1, starting with an at to `at` if it's not 0.
+    fn group_message(at: i64, text: &str) -> Event {
+        let mut message_chain = vec![json!({ "type": "Source", "id": 7, "time": 0 })];
+        if at != 0 {
+            message_chain.push(json!({ "type": "At", "target": at, "display": "" }));
+        }
+        message_chain.push(json!({ "type": "Plain", "text": text }));
+
+        serde_json::from_value(json!({
+            "type": "GroupMessage",
+            "sender": {
+                "id": 20211114,
+                "memberName": "Thungghuan",
+                "permission": "OWNER",
+                "group": { "id": 1, "name": "wood", "permission": "MEMBER" }
+            },
+            "messageChain": message_chain
+        }))
+        .unwrap()
+    }
 
-        Ok(())
+    #[test]
+    fn check_command_context() {
+        let mut bot = bot();
+
+        // an at is required in groups by default
+        let ctx = Context::new(bot.clone(), group_message(0, "/help")).unwrap();
+        assert!(!ctx.is_command());
+        let ctx = Context::new(bot.clone(), group_message(20211113, " /help me")).unwrap();
+        assert!(ctx.is_at_me() && ctx.is_command());
+        assert_eq!(ctx.command_name(), "help");
+        assert_eq!(ctx.message_chain(), MessageChain::from("me"));
+
+        bot.set_command_config(
+            CommandConfig::new()
+                .prefixes(&["!", "#"])
+                .nickname("wood")
+                .case_sensitive(false)
+                .group_require_at(2, false),
+        );
+
+        let ctx = Context::new(bot.clone(), group_message(0, "Wood, #Ban 1h")).unwrap();
+        assert!(ctx.is_at_me() && ctx.is_command());
+        assert_eq!(ctx.command_prefix(), "#");
+        assert_eq!(ctx.command_name(), "ban");
+        assert_eq!(
+            ctx.parse_args::<()>().unwrap_err().to_string(),
+            "Usage: #ban\nToo many arguments, unexpected `1h`."
+        );
+
+        // a sentence starting with the nickname is left untouched
+        let ctx = Context::new(bot.clone(), group_message(0, "wood is nice")).unwrap();
+        assert!(!ctx.is_at_me() && !ctx.is_command());
+        assert_eq!(ctx.message_chain(), MessageChain::from("wood is nice"));
+
+        let ctx = Context::new(bot.clone(), group_message(20211113, "/help")).unwrap();
+        assert!(ctx.is_at_me() && !ctx.is_command());
+        let ctx = Context::new(bot.clone(), group_message(0, "!help")).unwrap();
+        assert!(!ctx.is_at_me() && !ctx.is_command());
+        assert_eq!(
+            ctx.message_chain().first(),
+            Some(&SingleMessage::Plain {
+                text: "!help".to_string()
+            })
+        );
+
+        // no at is required in group 2
+        bot.set_command_config(CommandConfig::new().require_at(false));
+        let ctx = Context::new(bot, group_message(0, "/help")).unwrap();
+        assert!(ctx.is_command());
     }
 }
@@ -0,0 +1,554 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::event_listener::EVENT_TAGS;
+use crate::message::sender::{
+    FriendSender, Group, GroupSender, OtherClient, Permission, StrangerSender, TempSender,
+};
+use crate::message::MessageChain;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum SubjectKind {
+    Friend,
+    Group,
+    Stranger,
+}
+
+// Where a nudge happens.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Subject {
+    pub id: i64,
+    pub kind: SubjectKind,
+}
+
+// How to respond to a friend request, a member join request or a group invitation.
+#[derive(Debug, PartialEq, Clone)]
+pub enum RequestOperation {
+    Accept,
+    // the requests from the blacklist will be rejected automatically
+    Reject { blacklist: bool },
+    Ignore { blacklist: bool },
+}
+
+// Messages and events pushed by mirai-api-http 2.x.
+//
+// Events which are unknown to wood are kept as `Unknown` with the raw json,
+// so are the known ones which can't be parsed, with the error printed.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(remote = "Self", tag = "type")]
+pub enum Event {
+    // messages
+    #[serde(rename_all = "camelCase")]
+    FriendMessage {
+        sender: FriendSender,
+        message_chain: MessageChain,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    GroupMessage {
+        sender: GroupSender,
+        message_chain: MessageChain,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    TempMessage {
+        sender: TempSender,
+        message_chain: MessageChain,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    StrangerMessage {
+        sender: StrangerSender,
+        message_chain: MessageChain,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    OtherClientMessage {
+        sender: OtherClient,
+        message_chain: MessageChain,
+    },
+
+    // messages sent by the bot from other clients
+    #[serde(rename_all = "camelCase")]
+    FriendSyncMessage {
+        subject: FriendSender,
+        message_chain: MessageChain,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    GroupSyncMessage {
+        subject: Group,
+        message_chain: MessageChain,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    TempSyncMessage {
+        subject: TempSender,
+        message_chain: MessageChain,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    StrangerSyncMessage {
+        subject: StrangerSender,
+        message_chain: MessageChain,
+    },
+
+    // bot events
+    BotOnlineEvent {
+        qq: i64,
+    },
+
+    BotOfflineEventActive {
+        qq: i64,
+    },
+
+    BotOfflineEventForce {
+        qq: i64,
+    },
+
+    BotOfflineEventDropped {
+        qq: i64,
+    },
+
+    BotReloginEvent {
+        qq: i64,
+    },
+
+    // friend events
+    FriendInputStatusChangedEvent {
+        friend: FriendSender,
+        inputting: bool,
+    },
+
+    FriendNickChangedEvent {
+        friend: FriendSender,
+        from: String,
+        to: String,
+    },
+
+    FriendAddEvent {
+        friend: FriendSender,
+        stranger: bool,
+    },
+
+    FriendDeleteEvent {
+        friend: FriendSender,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    FriendRecallEvent {
+        author_id: i64,
+        message_id: i64,
+        time: i64,
+        operator: i64,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    NudgeEvent {
+        from_id: i64,
+        subject: Subject,
+        action: String,
+        suffix: String,
+        target: i64,
+    },
+
+    // group events
+    BotGroupPermissionChangeEvent {
+        origin: Permission,
+        current: Permission,
+        group: Group,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    BotMuteEvent {
+        duration_seconds: i64,
+        operator: GroupSender,
+    },
+
+    BotUnmuteEvent {
+        operator: GroupSender,
+    },
+
+    BotJoinGroupEvent {
+        group: Group,
+        invitor: Option<GroupSender>,
+    },
+
+    BotLeaveEventActive {
+        group: Group,
+    },
+
+    BotLeaveEventKick {
+        group: Group,
+        operator: Option<GroupSender>,
+    },
+
+    BotLeaveEventDisband {
+        group: Group,
+        operator: Option<GroupSender>,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    GroupRecallEvent {
+        author_id: i64,
+        message_id: i64,
+        time: i64,
+        group: Group,
+        operator: Option<GroupSender>,
+    },
+
+    GroupNameChangeEvent {
+        origin: String,
+        current: String,
+        group: Group,
+        operator: Option<GroupSender>,
+    },
+
+    GroupEntranceAnnouncementChangeEvent {
+        origin: String,
+        current: String,
+        group: Group,
+        operator: Option<GroupSender>,
+    },
+
+    GroupMuteAllEvent {
+        origin: bool,
+        current: bool,
+        group: Group,
+        operator: Option<GroupSender>,
+    },
+
+    GroupAllowAnonymousChatEvent {
+        origin: bool,
+        current: bool,
+        group: Group,
+        operator: Option<GroupSender>,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    GroupAllowConfessTalkEvent {
+        origin: bool,
+        current: bool,
+        group: Group,
+        is_by_bot: bool,
+    },
+
+    GroupAllowMemberInviteEvent {
+        origin: bool,
+        current: bool,
+        group: Group,
+        operator: Option<GroupSender>,
+    },
+
+    MemberJoinEvent {
+        member: GroupSender,
+        invitor: Option<GroupSender>,
+    },
+
+    MemberLeaveEventKick {
+        member: GroupSender,
+        operator: Option<GroupSender>,
+    },
+
+    MemberLeaveEventQuit {
+        member: GroupSender,
+    },
+
+    MemberCardChangeEvent {
+        origin: String,
+        current: String,
+        member: GroupSender,
+    },
+
+    MemberSpecialTitleChangeEvent {
+        origin: String,
+        current: String,
+        member: GroupSender,
+    },
+
+    MemberPermissionChangeEvent {
+        origin: Permission,
+        current: Permission,
+        member: GroupSender,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    MemberMuteEvent {
+        duration_seconds: i64,
+        member: GroupSender,
+        operator: Option<GroupSender>,
+    },
+
+    MemberUnmuteEvent {
+        member: GroupSender,
+        operator: Option<GroupSender>,
+    },
+
+    MemberHonorChangeEvent {
+        member: GroupSender,
+        action: String,
+        honor: String,
+    },
+
+    // requests
+    #[serde(rename_all = "camelCase")]
+    NewFriendRequestEvent {
+        event_id: i64,
+        from_id: i64,
+        group_id: i64,
+        nick: String,
+        message: String,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    MemberJoinRequestEvent {
+        event_id: i64,
+        from_id: i64,
+        group_id: i64,
+        group_name: String,
+        nick: String,
+        message: String,
+        invitor_id: Option<i64>,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    BotInvitedJoinGroupRequestEvent {
+        event_id: i64,
+        from_id: i64,
+        group_id: i64,
+        group_name: String,
+        nick: String,
+        message: String,
+    },
+
+    // other client events
+    OtherClientOnlineEvent {
+        client: OtherClient,
+        kind: Option<i64>,
+    },
+
+    OtherClientOfflineEvent {
+        client: OtherClient,
+    },
+
+    // console command events
+    CommandExecutedEvent {
+        name: String,
+        friend: Option<FriendSender>,
+        member: Option<GroupSender>,
+        args: MessageChain,
+    },
+
+    #[serde(skip)]
+    Unknown(Value),
+}
+
+impl<'de> Deserialize<'de> for Event {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // Never fail here, or the whole fetched batch will be lost.
+        let value = Value::deserialize(deserializer)?;
+
+        match parse_event(&value) {
+            Ok(event) => Ok(event),
+            Err(e) => {
+                eprintln!("[Error] Parsing event `{}`.\n{}", value["type"], e);
+                Ok(Event::Unknown(value))
+            }
+        }
+    }
+}
+
+// Only the events with an unrecognised `type` are `Unknown`,
+// the errors of the known ones are returned.
+fn parse_event(value: &Value) -> serde_json::Result<Event> {
+    let e = match Event::deserialize(value) {
+        Ok(event) => return Ok(event),
+        Err(e) => e,
+    };
+
+    match value["type"].as_str() {
+        Some(tag) if EVENT_TAGS.contains(&tag) => Err(e),
+        _ => Ok(Event::Unknown(value.clone())),
+    }
+}
+
+impl Serialize for Event {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Event::Unknown(value) => value.serialize(serializer),
+            _ => Event::serialize(self, serializer),
+        }
+    }
+}
+
+impl Event {
+    pub fn is_message(&self) -> bool {
+        matches!(
+            self,
+            Event::FriendMessage { .. }
+                | Event::GroupMessage { .. }
+                | Event::TempMessage { .. }
+                | Event::StrangerMessage { .. }
+        )
+    }
+
+    pub fn is_request(&self) -> bool {
+        matches!(
+            self,
+            Event::NewFriendRequestEvent { .. }
+                | Event::MemberJoinRequestEvent { .. }
+                | Event::BotInvitedJoinGroupRequestEvent { .. }
+        )
+    }
+
+    pub fn message_chain(&self) -> Option<&MessageChain> {
+        match self {
+            Event::FriendMessage { message_chain, .. }
+            | Event::GroupMessage { message_chain, .. }
+            | Event::TempMessage { message_chain, .. }
+            | Event::StrangerMessage { message_chain, .. }
+            | Event::OtherClientMessage { message_chain, .. }
+            | Event::FriendSyncMessage { message_chain, .. }
+            | Event::GroupSyncMessage { message_chain, .. }
+            | Event::TempSyncMessage { message_chain, .. }
+            | Event::StrangerSyncMessage { message_chain, .. } => Some(message_chain),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_event, Event, Subject, SubjectKind};
+    use crate::message::sender::{FriendSender, Group, GroupSender, Permission};
+    use crate::message::{MessageChain, SingleMessage};
+    use serde_json::json;
+
+    #[test]
+    fn check_received_friend_message_deserialize_result() {
+        let resp = r#"{
+            "type":"FriendMessage",
+            "messageChain":[{"type":"Source","id":7,"time":20211113},{"type":"Plain","text":"hi"}],
+            "sender":{"id":20211113,"nickname":"Thungghuan","remark":"Thungghuan"}
+        }"#;
+
+        let mut message_chain = MessageChain::new();
+        let source_message = SingleMessage::Source {
+            id: 7,
+            time: 20211113,
+        };
+        let plain_message = SingleMessage::Plain {
+            text: "hi".to_string(),
+        };
+        message_chain.push(source_message);
+        message_chain.push(plain_message);
+
+        let sender = FriendSender {
+            id: 20211113,
+            nickname: "Thungghuan".to_string(),
+            remark: "Thungghuan".to_string(),
+        };
+
+        let received_message = Event::FriendMessage {
+            sender,
+            message_chain,
+        };
+
+        assert_eq!(
+            serde_json::from_str::<Event>(resp).unwrap(),
+            received_message
+        );
+    }
+
+    #[test]
+    fn check_events_deserialize_result() {
+        let resp = r#"[
+            {
+                "type":"MemberJoinEvent",
+                "member":{
+                    "id":20211113,
+                    "memberName":"Thungghuan",
+                    "permission":"MEMBER",
+                    "group":{"id":20211113,"name":"木木","permission":"ADMINISTRATOR"}
+                },
+                "invitor":null
+            },
+            {
+                "type":"NudgeEvent",
+                "fromId":20211113,
+                "subject":{"id":20211114,"kind":"Group"},
+                "action":"戳了戳",
+                "suffix":"的脸",
+                "target":20211115
+            },
+            {
+                "type":"SomeEventFromTheFuture",
+                "foo":"bar"
+            }
+        ]"#;
+
+        let group = Group {
+            id: 20211113,
+            name: "木木".to_string(),
+            permission: Permission::ADMINISTRATOR,
+        };
+
+        let events = serde_json::from_str::<Vec<Event>>(resp).unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                Event::MemberJoinEvent {
+                    member: GroupSender {
+                        id: 20211113,
+                        member_name: "Thungghuan".to_string(),
+                        permission: Permission::MEMBER,
+                        group,
+                        special_title: "".to_string(),
+                        join_timestamp: 0,
+                        last_speak_timestamp: 0,
+                        mute_time_remaining: 0,
+                    },
+                    invitor: None,
+                },
+                Event::NudgeEvent {
+                    from_id: 20211113,
+                    subject: Subject {
+                        id: 20211114,
+                        kind: SubjectKind::Group,
+                    },
+                    action: "戳了戳".to_string(),
+                    suffix: "的脸".to_string(),
+                    target: 20211115,
+                },
+                Event::Unknown(json!({"type":"SomeEventFromTheFuture","foo":"bar"})),
+            ]
+        );
+
+        // the raw json is kept when serializing the unknown event
+        assert_eq!(
+            serde_json::to_value(&events[2]).unwrap(),
+            json!({"type":"SomeEventFromTheFuture","foo":"bar"})
+        );
+    }
+
+    #[test]
+    fn check_broken_event() {
+        let broken = json!({"type":"NudgeEvent","fromId":"not a number"});
+
+        assert!(parse_event(&broken).is_err());
+        assert_eq!(
+            serde_json::from_value::<Event>(broken.clone()).unwrap(),
+            Event::Unknown(broken)
+        );
+
+        let untyped = json!({"foo":"bar"});
+        assert_eq!(parse_event(&untyped).unwrap(), Event::Unknown(untyped));
+    }
+}
@@ -1,73 +1,206 @@
-use crate::{context::Context, error::Error, Result};
+use crate::command::CommandInfo;
+use crate::{context::Context, error::Error, event::Event, Result};
 use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
 
-#[derive(Clone)]
-pub enum EventType {
-    Message,
-    FriendMessage,
-    GroupMessage,
+// Every event can be listened by `bot.on(name, handler)`,
+// where the name is the camelCase event type without the `Event`.
+macro_rules! event_types {
+    ($($variant:ident => $name:literal,)*) => {
+        #[derive(Clone, PartialEq, Debug)]
+        pub enum EventType {
+            // all the messages except commands
+            Message,
+            Command,
+
+            $($variant,)*
+
+            // events unknown to wood, kept as raw json
+            Unknown,
+
+            Invalid(Error),
+        }
+
+        impl Display for EventType {
+            fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+                let event_type = match *self {
+                    EventType::Message => "message",
+                    EventType::Command => "command",
+                    $(EventType::$variant => $name,)*
+                    EventType::Unknown => "unknown",
+                    EventType::Invalid(_) => "invalidEvent",
+                };
 
-    Command,
+                write!(f, "{}", event_type)
+            }
+        }
 
-    Invalid(Error),
+        impl From<&str> for EventType {
+            fn from(event_type: &str) -> Self {
+                // match the &str to the enum variant
+                match event_type {
+                    "message" => EventType::Message,
+                    "command" => EventType::Command,
+                    $($name => EventType::$variant,)*
+                    "unknown" => EventType::Unknown,
+                    _ => {
+                        let msg = format!("Invalid event type: received `{}`, expected `message`, `command` or the name of a mirai event, e.g. `friendMessage` or `memberJoin`.", event_type);
+                        EventType::Invalid(Error::new(&msg))
+                    }
+                }
+            }
+        }
+
+        // The `type` of the events known to wood.
+        pub(crate) const EVENT_TAGS: &[&str] = &[$(stringify!($variant),)*];
+
+        impl From<&Event> for EventType {
+            fn from(event: &Event) -> Self {
+                match event {
+                    $(Event::$variant { .. } => EventType::$variant,)*
+                    Event::Unknown(_) => EventType::Unknown,
+                }
+            }
+        }
+    };
 }
 
-pub type EventHandler = dyn Fn(Context) -> Pin<Box<dyn Future<Output = Result<()>>>>;
+event_types! {
+    FriendMessage => "friendMessage",
+    GroupMessage => "groupMessage",
+    TempMessage => "tempMessage",
+    StrangerMessage => "strangerMessage",
+    OtherClientMessage => "otherClientMessage",
+    FriendSyncMessage => "friendSyncMessage",
+    GroupSyncMessage => "groupSyncMessage",
+    TempSyncMessage => "tempSyncMessage",
+    StrangerSyncMessage => "strangerSyncMessage",
 
-pub struct EventListener {
-    event_type: EventType,
-    handler: Box<EventHandler>,
+    BotOnlineEvent => "botOnline",
+    BotOfflineEventActive => "botOfflineActive",
+    BotOfflineEventForce => "botOfflineForce",
+    BotOfflineEventDropped => "botOfflineDropped",
+    BotReloginEvent => "botRelogin",
 
-    // this will be `None` if the event_type is not `Command`
-    command_name: Option<String>,
+    FriendInputStatusChangedEvent => "friendInputStatusChanged",
+    FriendNickChangedEvent => "friendNickChanged",
+    FriendAddEvent => "friendAdd",
+    FriendDeleteEvent => "friendDelete",
+    FriendRecallEvent => "friendRecall",
+    NudgeEvent => "nudge",
+
+    BotGroupPermissionChangeEvent => "botGroupPermissionChange",
+    BotMuteEvent => "botMute",
+    BotUnmuteEvent => "botUnmute",
+    BotJoinGroupEvent => "botJoinGroup",
+    BotLeaveEventActive => "botLeaveActive",
+    BotLeaveEventKick => "botLeaveKick",
+    BotLeaveEventDisband => "botLeaveDisband",
+    GroupRecallEvent => "groupRecall",
+    GroupNameChangeEvent => "groupNameChange",
+    GroupEntranceAnnouncementChangeEvent => "groupEntranceAnnouncementChange",
+    GroupMuteAllEvent => "groupMuteAll",
+    GroupAllowAnonymousChatEvent => "groupAllowAnonymousChat",
+    GroupAllowConfessTalkEvent => "groupAllowConfessTalk",
+    GroupAllowMemberInviteEvent => "groupAllowMemberInvite",
+    MemberJoinEvent => "memberJoin",
+    MemberLeaveEventKick => "memberLeaveKick",
+    MemberLeaveEventQuit => "memberLeaveQuit",
+    MemberCardChangeEvent => "memberCardChange",
+    MemberSpecialTitleChangeEvent => "memberSpecialTitleChange",
+    MemberPermissionChangeEvent => "memberPermissionChange",
+    MemberMuteEvent => "memberMute",
+    MemberUnmuteEvent => "memberUnmute",
+    MemberHonorChangeEvent => "memberHonorChange",
+
+    NewFriendRequestEvent => "newFriendRequest",
+    MemberJoinRequestEvent => "memberJoinRequest",
+    BotInvitedJoinGroupRequestEvent => "botInvitedJoinGroupRequest",
+
+    OtherClientOnlineEvent => "otherClientOnline",
+    OtherClientOfflineEvent => "otherClientOffline",
+
+    CommandExecutedEvent => "commandExecuted",
 }
 
-impl Display for EventType {
-    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        let event_type = match *self {
-            EventType::Message => "message",
-            EventType::FriendMessage => "friendMessage",
-            EventType::GroupMessage => "groupMessage",
-            EventType::Command => "command",
-            EventType::Invalid(_) => "invalidEvent",
-        };
+// Which listener failed, passed to the hook set by `bot.on_error(hook)`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ListenerInfo {
+    // the order in which the listener is added
+    pub index: usize,
+    pub priority: i32,
+    pub event_type: String,
+    pub command: Option<String>,
+
+    // whether it's the global middleware which failed, instead of a listener
+    pub middleware: bool,
+}
 
-        write!(f, "{}", event_type)
+impl ListenerInfo {
+    // The global middleware around the event, which can't be told apart by index.
+    pub(crate) fn middleware(ctx: &Context) -> Self {
+        ListenerInfo {
+            index: 0,
+            priority: 0,
+            event_type: EventType::from(ctx.event()).to_string(),
+            command: None,
+            middleware: true,
+        }
     }
 }
 
-impl From<&str> for EventType {
-    fn from(event_type: &str) -> Self {
-        // match the &str to the enum variant
-        match event_type {
-            "message" => EventType::Message,
-            "friendMessage" => EventType::FriendMessage,
-            "groupMessage" => EventType::GroupMessage,
-            "command" => EventType::Command,
-            _ => {
-                let msg = format!("Invalid message type: received `{}`, expected `message`, `friendMessage` or `groupMessage`.", event_type);
-                EventType::Invalid(Error::new(&msg))
-            }
+impl Display for ListenerInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.command {
+            _ if self.middleware => write!(f, "the middleware of `{}`", self.event_type),
+            Some(command) => write!(f, "the command `{}`", command),
+            None => write!(f, "the listener #{} of `{}`", self.index, self.event_type),
         }
     }
 }
 
+pub type EventHandler =
+    dyn Fn(Context) -> Pin<Box<dyn Future<Output = Result<()>> + Send>> + Send + Sync;
+
+#[derive(Clone)]
+pub struct EventListener {
+    event_type: EventType,
+    handler: Arc<EventHandler>,
+
+    // this will be `None` if the event_type is not `Command`
+    command: Option<CommandInfo>,
+
+    // the listeners with higher priority are called first
+    priority: i32,
+    index: usize,
+}
+
 impl EventListener {
-    pub fn new<F, Fut>(
-        event_type: EventType,
-        handler: &'static F,
-        command_name: Option<String>,
-    ) -> Self
+    pub fn new<F, Fut>(event_type: EventType, handler: F, command: Option<CommandInfo>) -> Self
     where
-        F: Fn(Context) -> Fut,
-        Fut: Future<Output = Result<()>> + 'static,
+        F: Fn(Context) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
     {
+        EventListener::from_handler(
+            event_type,
+            Box::new(move |ctx| Box::pin(handler(ctx))),
+            command,
+        )
+    }
+
+    pub fn from_handler(
+        event_type: EventType,
+        handler: Box<EventHandler>,
+        command: Option<CommandInfo>,
+    ) -> Self {
         EventListener {
             event_type,
-            handler: Box::new(|ctx| Box::pin(handler(ctx))),
-            command_name,
+            handler: handler.into(),
+            command,
+            priority: 0,
+            index: 0,
         }
     }
 
@@ -79,8 +212,31 @@ impl EventListener {
         (self.handler)(ctx).await
     }
 
-    pub fn command_name(&self) -> Option<String> {
-        self.command_name.clone()
+    pub fn command(&self) -> Option<&CommandInfo> {
+        self.command.as_ref()
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    pub fn set_index(&mut self, index: usize) {
+        self.index = index;
+    }
+
+    pub fn info(&self) -> ListenerInfo {
+        ListenerInfo {
+            index: self.index,
+            priority: self.priority,
+            event_type: self.event_type.to_string(),
+            command: self.command.as_ref().map(|command| command.name.clone()),
+            middleware: false,
+        }
     }
 }
 
@@ -88,6 +244,8 @@ impl EventListener {
 mod tests {
     use std::{future::Future, pin::Pin};
 
+    use super::EventType;
+    use crate::event::Event;
     use crate::Result;
 
     #[derive(Clone)]
@@ -144,4 +302,24 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn check_event_type_names() {
+        assert_eq!(EventType::from("nudge"), EventType::NudgeEvent);
+        assert_eq!(
+            EventType::from("memberLeaveKick"),
+            EventType::MemberLeaveEventKick
+        );
+        assert_eq!(
+            EventType::BotInvitedJoinGroupRequestEvent.to_string(),
+            "botInvitedJoinGroupRequest"
+        );
+        assert!(matches!(EventType::from("msg"), EventType::Invalid(_)));
+
+        let event = Event::BotOnlineEvent { qq: 20211113 };
+        assert_eq!(EventType::from(&event), EventType::BotOnlineEvent);
+
+        let event = Event::Unknown(serde_json::json!({"type": "SomeEventFromTheFuture"}));
+        assert_eq!(EventType::from(&event), EventType::Unknown);
+    }
 }
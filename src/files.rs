@@ -0,0 +1,361 @@
+use reqwest::multipart::{Form, Part};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::api::Api;
+use crate::error::Error;
+use crate::message::media::MediaSource;
+use crate::message::SingleMessage;
+use crate::Result;
+
+// The number of files fetched for each page by `list_all`.
+const PAGE_SIZE: i64 = 100;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadInfo {
+    pub sha1: String,
+    pub md5: String,
+    pub download_times: i64,
+    pub uploader_id: i64,
+    pub upload_time: i64,
+    pub last_modify_time: i64,
+    pub url: String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RemoteFile {
+    pub id: String,
+    pub name: String,
+    pub path: String,
+    pub size: i64,
+    // only exists when it's requested
+    pub download_info: Option<DownloadInfo>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RemoteDir {
+    pub id: String,
+    pub name: String,
+    pub path: String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum RemoteEntry {
+    File(RemoteFile),
+    Dir(RemoteDir),
+}
+
+// The file info returned by mirai.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FileInfo {
+    id: String,
+    name: String,
+    path: String,
+    is_file: bool,
+    #[serde(default)]
+    size: i64,
+    download_info: Option<DownloadInfo>,
+}
+
+impl From<FileInfo> for RemoteEntry {
+    fn from(info: FileInfo) -> Self {
+        if info.is_file {
+            RemoteEntry::File(RemoteFile {
+                id: info.id,
+                name: info.name,
+                path: info.path,
+                size: info.size,
+                download_info: info.download_info,
+            })
+        } else {
+            RemoteEntry::Dir(RemoteDir {
+                id: info.id,
+                name: info.name,
+                path: info.path,
+            })
+        }
+    }
+}
+
+fn parse_file(data: &Value) -> Result<RemoteFile> {
+    match serde_json::from_value::<FileInfo>(data.clone())?.into() {
+        RemoteEntry::File(file) => Ok(file),
+        RemoteEntry::Dir(_) => Err(Error::new("[Error] Expecting a file but got a directory.")),
+    }
+}
+
+fn parse_dir(data: &Value) -> Result<RemoteDir> {
+    match serde_json::from_value::<FileInfo>(data.clone())?.into() {
+        RemoteEntry::Dir(dir) => Ok(dir),
+        RemoteEntry::File(_) => Err(Error::new("[Error] Expecting a directory but got a file.")),
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FileParams {
+    session_key: String,
+    target: i64,
+    id: String,
+}
+
+// The files shared in a group, the id of the root directory is "".
+#[derive(Clone)]
+pub struct GroupFiles {
+    api: Api,
+    group: i64,
+}
+
+impl GroupFiles {
+    pub(crate) fn new(api: Api, group: i64) -> Self {
+        GroupFiles { api, group }
+    }
+
+    pub fn group(&self) -> i64 {
+        self.group
+    }
+
+    fn file_params(&self, id: &str) -> FileParams {
+        FileParams {
+            session_key: self.api.session().to_string(),
+            target: self.group,
+            id: id.to_string(),
+        }
+    }
+
+    // List a page of the directory, starting from `offset`.
+    pub async fn list(&self, dir_id: &str, offset: i64, size: i64) -> Result<Vec<RemoteEntry>> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params {
+            #[serde(flatten)]
+            file: FileParams,
+            offset: i64,
+            size: i64,
+        }
+
+        let query = Params {
+            file: self.file_params(dir_id),
+            offset,
+            size,
+        };
+
+        let resp = self.api.get("/file/list", &query).await?;
+        let infos: Vec<FileInfo> = serde_json::from_value(resp["data"].clone())?;
+
+        Ok(infos.into_iter().map(RemoteEntry::from).collect())
+    }
+
+    // List the whole directory page by page.
+    pub async fn list_all(&self, dir_id: &str) -> Result<Vec<RemoteEntry>> {
+        let mut entries = vec![];
+
+        loop {
+            let page = self.list(dir_id, entries.len() as i64, PAGE_SIZE).await?;
+            let is_last = (page.len() as i64) < PAGE_SIZE;
+
+            entries.extend(page);
+
+            if is_last {
+                return Ok(entries);
+            }
+        }
+    }
+
+    // The info of a file or a directory, with the download info of files.
+    pub async fn info(&self, id: &str) -> Result<RemoteEntry> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params {
+            #[serde(flatten)]
+            file: FileParams,
+            with_download_info: bool,
+        }
+
+        let query = Params {
+            file: self.file_params(id),
+            with_download_info: true,
+        };
+
+        let resp = self.api.get("/file/info", &query).await?;
+        let info: FileInfo = serde_json::from_value(resp["data"].clone())?;
+
+        Ok(info.into())
+    }
+
+    // Resolve a `File` element received in the group to its download url.
+    pub async fn download_url(&self, file: &SingleMessage) -> Result<String> {
+        let id = match file {
+            SingleMessage::File { id, .. } => id,
+            _ => {
+                return Err(Error::new(
+                    "[Error] Only `File` message has a download url.",
+                ))
+            }
+        };
+
+        match self.info(id).await? {
+            RemoteEntry::File(RemoteFile {
+                download_info: Some(download_info),
+                ..
+            }) => Ok(download_info.url),
+            _ => Err(Error::new("[Error] No download info of the file.")),
+        }
+    }
+
+    // Create a directory in `parent_id`, mirai only supports the directories in the root.
+    pub async fn mkdir(&self, parent_id: &str, name: &str) -> Result<RemoteDir> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params {
+            #[serde(flatten)]
+            file: FileParams,
+            directory_name: String,
+        }
+
+        let params = Params {
+            file: self.file_params(parent_id),
+            directory_name: name.to_string(),
+        };
+
+        let resp = self.api.post("/file/mkdir", &params).await?;
+
+        parse_dir(&resp["data"])
+    }
+
+    // Upload a file to the directory, always through http.
+    pub async fn upload<S: Into<MediaSource>>(
+        &self,
+        dir_id: &str,
+        name: &str,
+        file: S,
+    ) -> Result<RemoteFile> {
+        let file = file.into().into_bytes().await?;
+
+        let form = Form::new()
+            .text("type", "group")
+            .text("target", self.group.to_string())
+            .text("path", dir_id.to_string())
+            .part("file", Part::bytes(file).file_name(name.to_string()));
+
+        let resp = self.api.post_multipart("/file/upload", form).await?;
+
+        parse_file(&resp["data"])
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<()> {
+        self.api.post("/file/delete", &self.file_params(id)).await?;
+
+        Ok(())
+    }
+
+    pub async fn move_to(&self, id: &str, dir_id: &str) -> Result<()> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params {
+            #[serde(flatten)]
+            file: FileParams,
+            move_to: String,
+        }
+
+        let params = Params {
+            file: self.file_params(id),
+            move_to: dir_id.to_string(),
+        };
+
+        self.api.post("/file/move", &params).await?;
+
+        Ok(())
+    }
+
+    pub async fn rename(&self, id: &str, name: &str) -> Result<()> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params {
+            #[serde(flatten)]
+            file: FileParams,
+            rename_to: String,
+        }
+
+        let params = Params {
+            file: self.file_params(id),
+            rename_to: name.to_string(),
+        };
+
+        self.api.post("/file/rename", &params).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DownloadInfo, FileInfo, RemoteDir, RemoteEntry, RemoteFile};
+    use serde_json::json;
+
+    #[test]
+    fn check_file_info_into_entry() {
+        let infos: Vec<FileInfo> = serde_json::from_value(json!([
+            {
+                "name": "notes",
+                "id": "/dir",
+                "path": "/notes",
+                "parent": null,
+                "contact": {"id": 20211113, "name": "木木", "permission": "MEMBER"},
+                "isFile": false,
+                "isDirectory": true,
+                "downloadInfo": null
+            },
+            {
+                "name": "wood.pdf",
+                "id": "/file",
+                "path": "/wood.pdf",
+                "parent": null,
+                "contact": {"id": 20211113, "name": "木木", "permission": "MEMBER"},
+                "isFile": true,
+                "isDirectory": false,
+                "size": 1024,
+                "downloadInfo": {
+                    "sha1": "sha1",
+                    "md5": "md5",
+                    "downloadTimes": 1,
+                    "uploaderId": 20211113,
+                    "uploadTime": 20211113,
+                    "lastModifyTime": 20211113,
+                    "url": "https://mirai.host/wood.pdf"
+                }
+            }
+        ]))
+        .unwrap();
+
+        let entries: Vec<RemoteEntry> = infos.into_iter().map(RemoteEntry::from).collect();
+
+        assert_eq!(
+            entries,
+            vec![
+                RemoteEntry::Dir(RemoteDir {
+                    id: "/dir".to_string(),
+                    name: "notes".to_string(),
+                    path: "/notes".to_string(),
+                }),
+                RemoteEntry::File(RemoteFile {
+                    id: "/file".to_string(),
+                    name: "wood.pdf".to_string(),
+                    path: "/wood.pdf".to_string(),
+                    size: 1024,
+                    download_info: Some(DownloadInfo {
+                        sha1: "sha1".to_string(),
+                        md5: "md5".to_string(),
+                        download_times: 1,
+                        uploader_id: 20211113,
+                        upload_time: 20211113,
+                        last_modify_time: 20211113,
+                        url: "https://mirai.host/wood.pdf".to_string(),
+                    }),
+                }),
+            ]
+        );
+    }
+}
@@ -1,16 +1,38 @@
+// Make `::wood` work in the macros used inside this crate.
+extern crate self as wood;
+
 mod bot;
 pub use bot::Bot;
 
 mod api;
 
+mod adapter;
+pub use adapter::Adapter;
+
 mod error;
-pub use error::Result;
+pub use error::{Error, Result};
 
 pub mod message;
 
+pub mod event;
+
+pub mod files;
+
+pub mod announcement;
+
+pub mod command;
+pub use wood_macros::command;
+
 mod context;
+pub use context::Context;
+
+mod middleware;
+pub use middleware::{BoxFuture, Middleware, Next};
 
 mod event_listener;
+pub use event_listener::ListenerInfo;
+
+mod scheduler;
 
 mod utils;
 pub use utils::*;
@@ -0,0 +1,827 @@
+use std::collections::HashMap;
+
+use super::{MessageChain, SingleMessage};
+
+// Conversions between message chains and the string formats used by
+// mirai (`[mirai:at:123]`) and go-cqhttp (`[CQ:at,qq=123]`).
+//
+// Elements are written in the standard form when there is one,
+// which is lossy, the fields the form can't carry are dropped:
+// - the `display` of `At` and the `name` of `Face`, in both forms
+// - the `url` of images and flash images with an id, in mirai code
+// - everything of `Quote` except the `id`, in CQ code
+//
+// Elements without a standard form are written as json,
+// i.e. `[mirai:wood:{json}]` and `[CQ:wood,data={json}]`, so that they survive the round trip.
+// These forms are only understood by wood, not by mirai or go-cqhttp.
+impl MessageChain {
+    pub fn from_mirai_code(code: &str) -> Self {
+        let chars: Vec<char> = code.chars().collect();
+        let mut message_chain = MessageChain::new();
+        let mut text = String::new();
+        let mut i = 0;
+
+        while i < chars.len() {
+            if starts_with(&chars[i..], "[mirai:") {
+                if let Some(end) = find_unescaped(&chars, i + 7, ']') {
+                    let inner: String = chars[i + 7..end].iter().collect();
+
+                    if let Some(message) = parse_mirai_element(&inner) {
+                        push_text(&mut message_chain, &mut text);
+                        message_chain.push(message);
+                        i = end + 1;
+                        continue;
+                    }
+                }
+            }
+
+            if chars[i] == '\\' && i + 1 < chars.len() {
+                text.push(unescape_mirai_char(chars[i + 1]));
+                i += 2;
+                continue;
+            }
+
+            text.push(chars[i]);
+            i += 1;
+        }
+
+        push_text(&mut message_chain, &mut text);
+
+        message_chain
+    }
+
+    pub fn to_mirai_code(&self) -> String {
+        self.iter().map(to_mirai_element).collect()
+    }
+
+    pub fn from_cq_code(code: &str) -> Self {
+        let mut message_chain = MessageChain::new();
+        let mut text = String::new();
+        let mut rest = code;
+
+        while let Some(start) = rest.find("[CQ:") {
+            text.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            // `]` is always escaped inside a CQ code
+            let message = rest
+                .find(']')
+                .and_then(|end| parse_cq_element(&rest[4..end]).map(|message| (end, message)));
+
+            match message {
+                Some((end, message)) => {
+                    push_text(&mut message_chain, &mut unescape_cq(&text));
+                    text.clear();
+                    message_chain.push(message);
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    text.push('[');
+                    rest = &rest[1..];
+                }
+            }
+        }
+
+        text.push_str(rest);
+        push_text(&mut message_chain, &mut unescape_cq(&text));
+
+        message_chain
+    }
+
+    pub fn to_cq_code(&self) -> String {
+        self.iter().map(to_cq_element).collect()
+    }
+}
+
+fn push_text(message_chain: &mut MessageChain, text: &mut String) {
+    if !text.is_empty() {
+        message_chain.push(SingleMessage::Plain {
+            text: std::mem::take(text),
+        });
+    }
+}
+
+fn starts_with(chars: &[char], prefix: &str) -> bool {
+    let prefix: Vec<char> = prefix.chars().collect();
+    chars.len() >= prefix.len() && chars[..prefix.len()] == prefix[..]
+}
+
+// mirai code
+
+fn escape_mirai(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '\\' | '[' | ']' | ':' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn unescape_mirai_char(c: char) -> char {
+    match c {
+        'n' => '\n',
+        'r' => '\r',
+        _ => c,
+    }
+}
+
+fn unescape_mirai(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(c) => unescaped.push(unescape_mirai_char(c)),
+                None => unescaped.push('\\'),
+            },
+            _ => unescaped.push(c),
+        }
+    }
+
+    unescaped
+}
+
+fn find_unescaped(chars: &[char], from: usize, target: char) -> Option<usize> {
+    let mut i = from;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            c if c == target => return Some(i),
+            _ => i += 1,
+        }
+    }
+
+    None
+}
+
+// Split by the unescaped `sep`, the parts are still escaped.
+fn split_unescaped(text: &str, sep: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        let part = parts.last_mut().unwrap();
+
+        if c == '\\' {
+            part.push(c);
+            if let Some(c) = chars.next() {
+                part.push(c);
+            }
+        } else if c == sep {
+            parts.push(String::new());
+        } else {
+            part.push(c);
+        }
+    }
+
+    parts
+}
+
+fn mirai_code(kind: &str, args: &[&str]) -> String {
+    if args.is_empty() {
+        format!("[mirai:{}]", kind)
+    } else {
+        let args: Vec<String> = args.iter().map(|arg| escape_mirai(arg)).collect();
+        format!("[mirai:{}:{}]", kind, args.join(","))
+    }
+}
+
+fn to_mirai_element(message: &SingleMessage) -> String {
+    match message {
+        SingleMessage::Plain { text } => escape_mirai(text),
+        SingleMessage::At { target, .. } => mirai_code("at", &[&target.to_string()]),
+        SingleMessage::AtAll => mirai_code("atall", &[]),
+        SingleMessage::Face { face_id, .. } => mirai_code("face", &[&face_id.to_string()]),
+        SingleMessage::Image {
+            image_id: Some(image_id),
+            path: None,
+            base64: None,
+            ..
+        } => mirai_code("image", &[image_id]),
+        SingleMessage::FlashImage {
+            image_id: Some(image_id),
+            path: None,
+            base64: None,
+            ..
+        } => mirai_code("flash", &[image_id]),
+        SingleMessage::Dice { value } => mirai_code("dice", &[&value.to_string()]),
+        SingleMessage::App { content } => mirai_code("app", &[content]),
+        SingleMessage::Xml { xml } => mirai_code("service", &["60", xml]),
+        SingleMessage::Json { json } => mirai_code("service", &["1", json]),
+        SingleMessage::MusicShare {
+            kind,
+            title,
+            summary,
+            jump_url,
+            picture_url,
+            music_url,
+            brief,
+        } => mirai_code(
+            "musicshare",
+            &[
+                kind,
+                title,
+                summary,
+                jump_url,
+                picture_url,
+                music_url,
+                brief,
+            ],
+        ),
+        _ => mirai_code("wood", &[&serde_json::to_string(message).unwrap()]),
+    }
+}
+
+// Parse the content between `[mirai:` and `]`,
+// return `None` if it's not a valid element.
+fn parse_mirai_element(inner: &str) -> Option<SingleMessage> {
+    let (kind, args) = match inner.find(':') {
+        Some(index) => (&inner[..index], split_unescaped(&inner[index + 1..], ',')),
+        None => (inner, vec![]),
+    };
+    let args: Vec<String> = args.iter().map(|arg| unescape_mirai(arg)).collect();
+
+    let message = match (kind, args.as_slice()) {
+        ("at", [target]) => SingleMessage::At {
+            target: target.parse().ok()?,
+            display: "".to_string(),
+        },
+        ("atall", []) => SingleMessage::AtAll,
+        ("face", [face_id]) => SingleMessage::Face {
+            face_id: face_id.parse().ok()?,
+            name: "".to_string(),
+        },
+        ("image", [image_id]) => SingleMessage::Image {
+            image_id: Some(image_id.clone()),
+            url: None,
+            path: None,
+            base64: None,
+        },
+        ("flash", [image_id]) => SingleMessage::FlashImage {
+            image_id: Some(image_id.clone()),
+            url: None,
+            path: None,
+            base64: None,
+        },
+        ("dice", [value]) => SingleMessage::Dice {
+            value: value.parse().ok()?,
+        },
+        ("app", [content]) => SingleMessage::App {
+            content: content.clone(),
+        },
+        ("service", [id, content]) => match id.as_str() {
+            "60" => SingleMessage::Xml {
+                xml: content.clone(),
+            },
+            "1" => SingleMessage::Json {
+                json: content.clone(),
+            },
+            _ => return None,
+        },
+        ("musicshare", [kind, title, summary, jump_url, picture_url, music_url, brief]) => {
+            SingleMessage::MusicShare {
+                kind: kind.clone(),
+                title: title.clone(),
+                summary: summary.clone(),
+                jump_url: jump_url.clone(),
+                picture_url: picture_url.clone(),
+                music_url: music_url.clone(),
+                brief: brief.clone(),
+            }
+        }
+        ("wood", [json]) => serde_json::from_str(json).ok()?,
+        _ => return None,
+    };
+
+    Some(message)
+}
+
+// CQ code
+
+fn escape_cq(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('[', "&#91;")
+        .replace(']', "&#93;")
+}
+
+fn escape_cq_param(value: &str) -> String {
+    escape_cq(value).replace(',', "&#44;")
+}
+
+// `&amp;` must be the last, or `&amp;#91;` will become `[`.
+fn unescape_cq(text: &str) -> String {
+    text.replace("&#44;", ",")
+        .replace("&#91;", "[")
+        .replace("&#93;", "]")
+        .replace("&amp;", "&")
+}
+
+fn cq_code(kind: &str, params: &[(&str, &str)]) -> String {
+    let mut code = format!("[CQ:{}", kind);
+
+    for (key, value) in params {
+        code.push_str(&format!(",{}={}", key, escape_cq_param(value)));
+    }
+
+    code.push(']');
+    code
+}
+
+// The `file` of images and voices is the id, the path or the base64.
+fn cq_file(id: &Option<String>, path: &Option<String>, base64: &Option<String>) -> Option<String> {
+    match (id, path, base64) {
+        (Some(id), _, _) => Some(id.clone()),
+        (None, Some(path), _) => Some(format!("file://{}", path)),
+        (None, None, Some(base64)) => Some(format!("base64://{}", base64)),
+        _ => None,
+    }
+}
+
+type CqFile = (
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+);
+
+// Return `(id, url, path, base64)`.
+fn parse_cq_file(params: &HashMap<String, String>) -> CqFile {
+    let (mut id, mut path, mut base64) = (None, None, None);
+
+    if let Some(file) = params.get("file") {
+        if let Some(file_path) = file.strip_prefix("file://") {
+            path = Some(file_path.to_string());
+        } else if let Some(file_base64) = file.strip_prefix("base64://") {
+            base64 = Some(file_base64.to_string());
+        } else {
+            id = Some(file.clone());
+        }
+    }
+
+    (id, params.get("url").cloned(), path, base64)
+}
+
+fn to_cq_media(
+    kind: &str,
+    id: &Option<String>,
+    url: &Option<String>,
+    path: &Option<String>,
+    base64: &Option<String>,
+    extra: &[(&str, &str)],
+) -> String {
+    let file = cq_file(id, path, base64);
+    let mut params = vec![];
+
+    if let Some(file) = &file {
+        params.push(("file", file.as_str()));
+    }
+    if let Some(url) = url {
+        params.push(("url", url.as_str()));
+    }
+    params.extend_from_slice(extra);
+
+    cq_code(kind, &params)
+}
+
+fn to_cq_element(message: &SingleMessage) -> String {
+    match message {
+        SingleMessage::Plain { text } => escape_cq(text),
+        SingleMessage::At { target, .. } => cq_code("at", &[("qq", &target.to_string())]),
+        SingleMessage::AtAll => cq_code("at", &[("qq", "all")]),
+        SingleMessage::Face { face_id, .. } => cq_code("face", &[("id", &face_id.to_string())]),
+        SingleMessage::Quote { id, .. } => cq_code("reply", &[("id", &id.to_string())]),
+        SingleMessage::Image {
+            image_id,
+            url,
+            path,
+            base64,
+        } => to_cq_media("image", image_id, url, path, base64, &[]),
+        SingleMessage::FlashImage {
+            image_id,
+            url,
+            path,
+            base64,
+        } => to_cq_media("image", image_id, url, path, base64, &[("type", "flash")]),
+        SingleMessage::Voice {
+            voice_id,
+            url,
+            path,
+            base64,
+            ..
+        } => to_cq_media("record", voice_id, url, path, base64, &[]),
+        SingleMessage::Xml { xml } => cq_code("xml", &[("data", xml)]),
+        SingleMessage::Json { json } => cq_code("json", &[("data", json)]),
+        SingleMessage::Dice { value } => cq_code("dice", &[("value", &value.to_string())]),
+        SingleMessage::MusicShare {
+            kind,
+            title,
+            summary,
+            jump_url,
+            picture_url,
+            music_url,
+            brief,
+        } => cq_code(
+            "music",
+            &[
+                ("type", "custom"),
+                ("url", jump_url),
+                ("audio", music_url),
+                ("title", title),
+                ("content", summary),
+                ("image", picture_url),
+                // not in the standard, but needed by mirai
+                ("kind", kind),
+                ("brief", brief),
+            ],
+        ),
+        _ => cq_code(
+            "wood",
+            &[("data", &serde_json::to_string(message).unwrap())],
+        ),
+    }
+}
+
+// Parse the content between `[CQ:` and `]`,
+// return `None` if it's not a valid element.
+fn parse_cq_element(inner: &str) -> Option<SingleMessage> {
+    let mut parts = inner.split(',');
+    let kind = parts.next()?;
+
+    let mut params = HashMap::new();
+    for part in parts {
+        let (key, value) = part.split_once('=')?;
+        params.insert(key.to_string(), unescape_cq(value));
+    }
+    let param = |key: &str| params.get(key).cloned();
+
+    let message = match kind {
+        "at" => match param("qq")?.as_str() {
+            "all" => SingleMessage::AtAll,
+            qq => SingleMessage::At {
+                target: qq.parse().ok()?,
+                display: "".to_string(),
+            },
+        },
+        "face" => SingleMessage::Face {
+            face_id: param("id")?.parse().ok()?,
+            name: "".to_string(),
+        },
+        "reply" => SingleMessage::Quote {
+            id: param("id")?.parse().ok()?,
+            group_id: 0,
+            sender_id: 0,
+            target_id: 0,
+            origin: MessageChain::new(),
+        },
+        "image" => {
+            let (image_id, url, path, base64) = parse_cq_file(&params);
+
+            match param("type").as_deref() {
+                Some("flash") => SingleMessage::FlashImage {
+                    image_id,
+                    url,
+                    path,
+                    base64,
+                },
+                _ => SingleMessage::Image {
+                    image_id,
+                    url,
+                    path,
+                    base64,
+                },
+            }
+        }
+        "record" => {
+            let (voice_id, url, path, base64) = parse_cq_file(&params);
+
+            SingleMessage::Voice {
+                voice_id,
+                url,
+                path,
+                base64,
+                length: None,
+            }
+        }
+        "xml" => SingleMessage::Xml {
+            xml: param("data")?,
+        },
+        "json" => SingleMessage::Json {
+            json: param("data")?,
+        },
+        "dice" => SingleMessage::Dice {
+            value: param("value")?.parse().ok()?,
+        },
+        "music" => SingleMessage::MusicShare {
+            kind: param("kind").unwrap_or_default(),
+            title: param("title")?,
+            summary: param("content").unwrap_or_default(),
+            jump_url: param("url")?,
+            picture_url: param("image").unwrap_or_default(),
+            music_url: param("audio")?,
+            brief: param("brief").unwrap_or_default(),
+        },
+        "wood" => serde_json::from_str(&param("data")?).ok()?,
+        _ => return None,
+    };
+
+    Some(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{ForwardNode, MessageChain, SingleMessage};
+    use proptest::prelude::*;
+    use serde_json::json;
+
+    #[test]
+    fn check_mirai_code() {
+        let message_chain = MessageChain::new()
+            .text("hello [world]: 1, 2\\\n")
+            .at(20211113)
+            .at_all()
+            .face(1)
+            .message(SingleMessage::Xml {
+                xml: "<msg a=\"1\"/>".to_string(),
+            });
+
+        let code = message_chain.to_mirai_code();
+        assert_eq!(
+            code,
+            "hello \\[world\\]\\: 1\\, 2\\\\\\n[mirai:at:20211113][mirai:atall][mirai:face:1][mirai:service:60,<msg a=\"1\"/>]"
+        );
+        assert_eq!(MessageChain::from_mirai_code(&code), message_chain);
+
+        // invalid codes are kept as plain text
+        assert_eq!(
+            MessageChain::from_mirai_code("[mirai:at:abc][mirai:unknown]"),
+            MessageChain::from("[mirai:at:abc][mirai:unknown]")
+        );
+    }
+
+    #[test]
+    fn check_cq_code() {
+        let message_chain = MessageChain::new()
+            .text("a&b [c], d")
+            .at(20211113)
+            .at_all()
+            .image_url("https://mirai.host/image.png?a=1,2");
+
+        let code = message_chain.to_cq_code();
+        assert_eq!(
+            code,
+            "a&amp;b &#91;c&#93;, d[CQ:at,qq=20211113][CQ:at,qq=all][CQ:image,url=https://mirai.host/image.png?a=1&#44;2]"
+        );
+        assert_eq!(MessageChain::from_cq_code(&code), message_chain);
+
+        assert_eq!(
+            MessageChain::from_cq_code("[CQ:reply,id=7]hi [CQ:unknown]"),
+            MessageChain::from(vec![
+                SingleMessage::Quote {
+                    id: 7,
+                    group_id: 0,
+                    sender_id: 0,
+                    target_id: 0,
+                    origin: MessageChain::new(),
+                },
+                SingleMessage::Plain {
+                    text: "hi [CQ:unknown]".to_string(),
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn check_lossy_fields() {
+        let message_chain = MessageChain::from(vec![
+            SingleMessage::At {
+                target: 20211113,
+                display: "@Thungghuan".to_string(),
+            },
+            SingleMessage::Face {
+                face_id: 1,
+                name: "smile".to_string(),
+            },
+            SingleMessage::Image {
+                image_id: Some("{01E9451B}.png".to_string()),
+                url: Some("https://mirai.host/image.png".to_string()),
+                path: None,
+                base64: None,
+            },
+            SingleMessage::Quote {
+                id: 7,
+                group_id: 1,
+                sender_id: 2,
+                target_id: 3,
+                origin: MessageChain::from("hi"),
+            },
+        ]);
+
+        let dropped = |keep_url: bool, keep_quote: bool| {
+            MessageChain::from(vec![
+                SingleMessage::At {
+                    target: 20211113,
+                    display: "".to_string(),
+                },
+                SingleMessage::Face {
+                    face_id: 1,
+                    name: "".to_string(),
+                },
+                SingleMessage::Image {
+                    image_id: Some("{01E9451B}.png".to_string()),
+                    url: keep_url.then(|| "https://mirai.host/image.png".to_string()),
+                    path: None,
+                    base64: None,
+                },
+                if keep_quote {
+                    message_chain[3].clone()
+                } else {
+                    SingleMessage::Quote {
+                        id: 7,
+                        group_id: 0,
+                        sender_id: 0,
+                        target_id: 0,
+                        origin: MessageChain::new(),
+                    }
+                },
+            ])
+        };
+
+        // the quote is kept in the wood form of mirai code
+        assert_eq!(
+            MessageChain::from_mirai_code(&message_chain.to_mirai_code()),
+            dropped(false, true)
+        );
+        assert_eq!(
+            MessageChain::from_cq_code(&message_chain.to_cq_code()),
+            dropped(true, false)
+        );
+    }
+
+    // Strings with all the characters that need to be escaped.
+    fn arb_string() -> impl Strategy<Value = String> {
+        "[a-z0-9 \\[\\]:,\\\\&#;=/\n木]{0,8}"
+    }
+
+    fn arb_option_string() -> impl Strategy<Value = Option<String>> {
+        prop::option::of(arb_string())
+    }
+
+    // Images and voices carry at most one of the id, the path and the base64,
+    // and the url is dropped by mirai code when there is an id.
+    fn arb_media() -> impl Strategy<
+        Value = (
+            Option<String>,
+            Option<String>,
+            Option<String>,
+            Option<String>,
+        ),
+    > {
+        (0..4usize, arb_string(), arb_option_string()).prop_map(|(kind, value, url)| match kind {
+            0 => (Some(value), None, None, None),
+            1 => (None, url, Some(value), None),
+            2 => (None, url, None, Some(value)),
+            _ => (None, url, None, None),
+        })
+    }
+
+    // Elements with the fields dropped by the standard forms left empty.
+    fn arb_single_message() -> impl Strategy<Value = SingleMessage> {
+        prop_oneof![
+            arb_string()
+                .prop_filter("empty text is dropped", |text| !text.is_empty())
+                .prop_map(|text| SingleMessage::Plain { text }),
+            any::<i64>().prop_map(|target| SingleMessage::At {
+                target,
+                display: "".to_string()
+            }),
+            Just(SingleMessage::AtAll),
+            any::<i64>().prop_map(|face_id| SingleMessage::Face {
+                face_id,
+                name: "".to_string()
+            }),
+            (any::<i64>(), any::<i64>()).prop_map(|(id, time)| SingleMessage::Source { id, time }),
+            any::<i64>().prop_map(|id| SingleMessage::Quote {
+                id,
+                group_id: 0,
+                sender_id: 0,
+                target_id: 0,
+                origin: MessageChain::new(),
+            }),
+            arb_media().prop_map(|(image_id, url, path, base64)| SingleMessage::Image {
+                image_id,
+                url,
+                path,
+                base64
+            }),
+            arb_media().prop_map(|(image_id, url, path, base64)| SingleMessage::FlashImage {
+                image_id,
+                url,
+                path,
+                base64
+            }),
+            arb_media().prop_map(|(voice_id, url, path, base64)| SingleMessage::Voice {
+                voice_id,
+                url,
+                path,
+                base64,
+                length: None,
+            }),
+            arb_string().prop_map(|xml| SingleMessage::Xml { xml }),
+            arb_string().prop_map(|json| SingleMessage::Json { json }),
+            arb_string().prop_map(|content| SingleMessage::App { content }),
+            arb_string().prop_map(|name| SingleMessage::Poke { name }),
+            any::<i64>().prop_map(|value| SingleMessage::Dice { value }),
+            (any::<i64>(), arb_string())
+                .prop_map(|(id, name)| SingleMessage::MarketFace { id, name }),
+            (
+                arb_string(),
+                arb_string(),
+                arb_string(),
+                arb_string(),
+                arb_string(),
+                arb_string(),
+                arb_string()
+            )
+                .prop_map(
+                    |(kind, title, summary, jump_url, picture_url, music_url, brief)| {
+                        SingleMessage::MusicShare {
+                            kind,
+                            title,
+                            summary,
+                            jump_url,
+                            picture_url,
+                            music_url,
+                            brief,
+                        }
+                    }
+                ),
+            (any::<i64>(), arb_string()).prop_map(|(message_id, text)| SingleMessage::Forward {
+                node_list: vec![ForwardNode {
+                    sender_id: None,
+                    time: None,
+                    sender_name: Some(text.clone()),
+                    message_chain: Some(MessageChain::from(text)),
+                    message_id: Some(message_id),
+                }],
+            }),
+            (arb_string(), arb_string(), any::<i64>())
+                .prop_map(|(id, name, size)| SingleMessage::File { id, name, size }),
+            (arb_string(), arb_option_string(), any::<Option<i64>>()).prop_map(
+                |(video_id, filename, file_size)| SingleMessage::ShortVideo {
+                    video_id,
+                    filename,
+                    file_md5: None,
+                    file_size,
+                    file_format: None,
+                    video_url: None,
+                }
+            ),
+            arb_string().prop_map(|code| SingleMessage::MiraiCode { code }),
+            arb_string()
+                .prop_map(|foo| SingleMessage::Unknown(json!({"type": "Custom", "foo": foo}))),
+        ]
+    }
+
+    // Adjacent plain texts are merged when parsing.
+    fn arb_message_chain() -> impl Strategy<Value = MessageChain> {
+        prop::collection::vec(arb_single_message(), 0..8).prop_map(|messages| {
+            let mut message_chain = MessageChain::new();
+
+            for message in messages {
+                match (message_chain.last_mut(), message) {
+                    (Some(SingleMessage::Plain { text }), SingleMessage::Plain { text: more }) => {
+                        text.push_str(&more)
+                    }
+                    (_, message) => message_chain.push(message),
+                }
+            }
+
+            message_chain
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn mirai_code_round_trip(message_chain in arb_message_chain()) {
+            let code = message_chain.to_mirai_code();
+            prop_assert_eq!(MessageChain::from_mirai_code(&code), message_chain);
+        }
+
+        #[test]
+        fn cq_code_round_trip(message_chain in arb_message_chain()) {
+            let code = message_chain.to_cq_code();
+            prop_assert_eq!(MessageChain::from_cq_code(&code), message_chain);
+        }
+    }
+}
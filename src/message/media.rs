@@ -0,0 +1,206 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::path::PathBuf;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use super::SingleMessage;
+use crate::Result;
+
+// The content of an image, a voice or a short video to upload.
+pub enum MediaSource {
+    Path(PathBuf),
+    Bytes(Vec<u8>),
+    Reader(Box<dyn AsyncRead + Send + Unpin>),
+}
+
+impl MediaSource {
+    pub fn reader<R: AsyncRead + Send + Unpin + 'static>(reader: R) -> Self {
+        MediaSource::Reader(Box::new(reader))
+    }
+
+    // The whole content is read into memory, which is needed by the hash anyway.
+    pub(crate) async fn into_bytes(self) -> Result<Vec<u8>> {
+        match self {
+            MediaSource::Path(path) => Ok(tokio::fs::read(path).await?),
+            MediaSource::Bytes(bytes) => Ok(bytes),
+            MediaSource::Reader(mut reader) => {
+                let mut bytes = vec![];
+                reader.read_to_end(&mut bytes).await?;
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+impl From<PathBuf> for MediaSource {
+    fn from(path: PathBuf) -> Self {
+        MediaSource::Path(path)
+    }
+}
+
+impl From<&std::path::Path> for MediaSource {
+    fn from(path: &std::path::Path) -> Self {
+        MediaSource::Path(path.to_path_buf())
+    }
+}
+
+impl From<&str> for MediaSource {
+    fn from(path: &str) -> Self {
+        MediaSource::Path(PathBuf::from(path))
+    }
+}
+
+impl From<Vec<u8>> for MediaSource {
+    fn from(bytes: Vec<u8>) -> Self {
+        MediaSource::Bytes(bytes)
+    }
+}
+
+impl From<&[u8]> for MediaSource {
+    fn from(bytes: &[u8]) -> Self {
+        MediaSource::Bytes(bytes.to_vec())
+    }
+}
+
+// Ids returned by mirai after uploading,
+// which can be pushed into the message chain directly.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct ImageId(pub String);
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct VoiceId(pub String);
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct VideoId(pub String);
+
+impl From<ImageId> for SingleMessage {
+    fn from(id: ImageId) -> Self {
+        SingleMessage::Image {
+            image_id: Some(id.0),
+            url: None,
+            path: None,
+            base64: None,
+        }
+    }
+}
+
+impl From<VoiceId> for SingleMessage {
+    fn from(id: VoiceId) -> Self {
+        SingleMessage::Voice {
+            voice_id: Some(id.0),
+            url: None,
+            path: None,
+            base64: None,
+            length: None,
+        }
+    }
+}
+
+impl From<VideoId> for SingleMessage {
+    fn from(id: VideoId) -> Self {
+        SingleMessage::ShortVideo {
+            video_id: id.0,
+            filename: None,
+            file_md5: None,
+            file_size: None,
+            file_format: None,
+            video_url: None,
+        }
+    }
+}
+
+// The SHA-256 of the content, which is the key of the upload cache.
+pub(crate) fn content_hash(bytes: &[u8]) -> [u8; 32] {
+    Sha256::digest(bytes).into()
+}
+
+// The uploaded ids, the least recently used one is evicted when it's full.
+pub(crate) struct UploadCache<K> {
+    capacity: usize,
+    tick: u64,
+    // the id and when it's used last time
+    entries: HashMap<K, (String, u64)>,
+}
+
+impl<K: Hash + Eq> UploadCache<K> {
+    pub fn new(capacity: usize) -> Self {
+        UploadCache {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, key: &K) -> Option<String> {
+        self.tick += 1;
+
+        let (id, used) = self.entries.get_mut(key)?;
+        *used = self.tick;
+        Some(id.clone())
+    }
+
+    pub fn insert(&mut self, key: K, id: String) {
+        self.tick += 1;
+
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            // the ticks are unique, so only the oldest entry is removed
+            if let Some(oldest) = self.entries.values().map(|(_, used)| *used).min() {
+                self.entries.retain(|_, (_, used)| *used != oldest);
+            }
+        }
+
+        self.entries.insert(key, (id, self.tick));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{content_hash, ImageId, MediaSource, UploadCache};
+    use crate::message::{MessageChain, SingleMessage};
+
+    #[tokio::test]
+    async fn check_media_source() {
+        let bytes = MediaSource::from(&b"wood"[..]).into_bytes().await.unwrap();
+        assert_eq!(bytes, b"wood");
+
+        let bytes = MediaSource::reader(&b"wood"[..])
+            .into_bytes()
+            .await
+            .unwrap();
+        assert_eq!(bytes, b"wood");
+
+        assert_eq!(content_hash(b"wood"), content_hash(&bytes));
+        assert_ne!(content_hash(b"wood"), content_hash(b"mumu"));
+    }
+
+    #[test]
+    fn check_upload_cache() {
+        let mut cache = UploadCache::new(2);
+        cache.insert("a", "1".to_string());
+        cache.insert("b", "2".to_string());
+
+        // `a` is used later than `b`, so `b` is evicted
+        assert_eq!(cache.get(&"a"), Some("1".to_string()));
+        cache.insert("c", "3".to_string());
+
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.get(&"a"), Some("1".to_string()));
+        assert_eq!(cache.get(&"c"), Some("3".to_string()));
+    }
+
+    #[test]
+    fn check_id_into_message() {
+        let message_chain = MessageChain::new().message(ImageId("{01E9451B}.jpg".to_string()));
+
+        assert_eq!(
+            message_chain[0],
+            SingleMessage::Image {
+                image_id: Some("{01E9451B}.jpg".to_string()),
+                url: None,
+                path: None,
+                base64: None,
+            }
+        );
+    }
+}
@@ -1,18 +1,36 @@
-use serde::{Deserialize, Serialize};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::ops::{Deref, DerefMut};
 
+// Message elements of mirai-api-http 2.x.
+//
+// Elements which are unknown to wood, or can't be parsed,
+// are kept as `Unknown` with the raw json.
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
-#[serde(tag = "type")]
+#[serde(remote = "Self", tag = "type")]
 pub enum SingleMessage {
     Source {
         id: i64,
         time: i64,
     },
 
+    #[serde(rename_all = "camelCase")]
+    Quote {
+        id: i64,
+        group_id: i64,
+        sender_id: i64,
+        target_id: i64,
+        origin: MessageChain,
+    },
+
     At {
         target: i64,
         display: String,
     },
 
+    AtAll,
+
     Plain {
         text: String,
     },
@@ -30,20 +48,327 @@ pub enum SingleMessage {
         path: Option<String>,
         base64: Option<String>,
     },
+
+    #[serde(rename_all = "camelCase")]
+    FlashImage {
+        image_id: Option<String>,
+        url: Option<String>,
+        path: Option<String>,
+        base64: Option<String>,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    Voice {
+        voice_id: Option<String>,
+        url: Option<String>,
+        path: Option<String>,
+        base64: Option<String>,
+        length: Option<i64>,
+    },
+
+    Xml {
+        xml: String,
+    },
+
+    Json {
+        json: String,
+    },
+
+    App {
+        content: String,
+    },
+
+    Poke {
+        name: String,
+    },
+
+    Dice {
+        value: i64,
+    },
+
+    MarketFace {
+        id: i64,
+        name: String,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    MusicShare {
+        kind: String,
+        title: String,
+        summary: String,
+        jump_url: String,
+        picture_url: String,
+        music_url: String,
+        brief: String,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    Forward {
+        node_list: Vec<ForwardNode>,
+    },
+
+    File {
+        id: String,
+        name: String,
+        size: i64,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    ShortVideo {
+        video_id: String,
+        filename: Option<String>,
+        file_md5: Option<String>,
+        file_size: Option<i64>,
+        file_format: Option<String>,
+        video_url: Option<String>,
+    },
+
+    MiraiCode {
+        code: String,
+    },
+
+    #[serde(skip)]
+    Unknown(Value),
+}
+
+// A message in the forward message, which can either be
+// given by its content or referred by `message_id`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ForwardNode {
+    pub sender_id: Option<i64>,
+    pub time: Option<i64>,
+    pub sender_name: Option<String>,
+    pub message_chain: Option<MessageChain>,
+    pub message_id: Option<i64>,
 }
 
-pub type MessageChain = Vec<SingleMessage>;
+impl<'de> Deserialize<'de> for SingleMessage {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // Never fail here, or the whole message will be lost.
+        let value = Value::deserialize(deserializer)?;
+
+        Ok(SingleMessage::deserialize(&value).unwrap_or(SingleMessage::Unknown(value)))
+    }
+}
+
+impl Serialize for SingleMessage {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            SingleMessage::Unknown(value) => value.serialize(serializer),
+            _ => SingleMessage::serialize(self, serializer),
+        }
+    }
+}
+
+// A list of message elements, which is serialized as a json array.
+//
+// let message_chain = MessageChain::new().at(qq).text(" hello").face(1);
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(transparent)]
+pub struct MessageChain(Vec<SingleMessage>);
+
+impl MessageChain {
+    pub fn new() -> Self {
+        MessageChain(vec![])
+    }
+
+    pub fn push(&mut self, message: SingleMessage) {
+        self.0.push(message)
+    }
+
+    pub fn insert(&mut self, index: usize, message: SingleMessage) {
+        self.0.insert(index, message)
+    }
+
+    pub fn remove(&mut self, index: usize) -> SingleMessage {
+        self.0.remove(index)
+    }
+
+    pub fn into_vec(self) -> Vec<SingleMessage> {
+        self.0
+    }
+
+    // builders
+
+    pub fn message<T: Into<SingleMessage>>(mut self, message: T) -> Self {
+        self.push(message.into());
+        self
+    }
+
+    pub fn text<T: Into<String>>(self, text: T) -> Self {
+        self.message(SingleMessage::Plain { text: text.into() })
+    }
+
+    pub fn at(self, target: i64) -> Self {
+        self.message(SingleMessage::At {
+            target,
+            display: "".to_string(),
+        })
+    }
+
+    pub fn at_all(self) -> Self {
+        self.message(SingleMessage::AtAll)
+    }
+
+    pub fn face(self, face_id: i64) -> Self {
+        self.message(SingleMessage::Face {
+            face_id,
+            name: "".to_string(),
+        })
+    }
+
+    pub fn image_url<T: Into<String>>(self, url: T) -> Self {
+        self.message(SingleMessage::Image {
+            image_id: None,
+            url: Some(url.into()),
+            path: None,
+            base64: None,
+        })
+    }
+
+    // The path is resolved by mirai, relative to its working directory.
+    pub fn image_path<T: Into<String>>(self, path: T) -> Self {
+        self.message(SingleMessage::Image {
+            image_id: None,
+            url: None,
+            path: Some(path.into()),
+            base64: None,
+        })
+    }
+
+    pub fn image_bytes<T: AsRef<[u8]>>(self, bytes: T) -> Self {
+        self.message(SingleMessage::Image {
+            image_id: None,
+            url: None,
+            path: None,
+            base64: Some(BASE64.encode(bytes)),
+        })
+    }
+
+    // accessors
+
+    // All the plain text joined together.
+    pub fn plain_text(&self) -> String {
+        self.iter()
+            .filter_map(|message| match message {
+                SingleMessage::Plain { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // Both images and flash images.
+    pub fn images(&self) -> Vec<&SingleMessage> {
+        self.iter()
+            .filter(|message| {
+                matches!(
+                    message,
+                    SingleMessage::Image { .. } | SingleMessage::FlashImage { .. }
+                )
+            })
+            .collect()
+    }
+
+    // The qq numbers which are mentioned by `At`.
+    pub fn mentions(&self) -> Vec<i64> {
+        self.iter()
+            .filter_map(|message| match message {
+                SingleMessage::At { target, .. } => Some(*target),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // The `(id, time)` of the source, which only exists in received messages.
+    pub fn source(&self) -> Option<(i64, i64)> {
+        self.iter().find_map(|message| match message {
+            SingleMessage::Source { id, time } => Some((*id, *time)),
+            _ => None,
+        })
+    }
+}
+
+impl Deref for MessageChain {
+    type Target = [SingleMessage];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for MessageChain {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl From<&str> for MessageChain {
+    fn from(text: &str) -> Self {
+        MessageChain::new().text(text)
+    }
+}
+
+impl From<String> for MessageChain {
+    fn from(text: String) -> Self {
+        MessageChain::new().text(text)
+    }
+}
+
+impl From<SingleMessage> for MessageChain {
+    fn from(message: SingleMessage) -> Self {
+        MessageChain(vec![message])
+    }
+}
+
+impl From<Vec<SingleMessage>> for MessageChain {
+    fn from(messages: Vec<SingleMessage>) -> Self {
+        MessageChain(messages)
+    }
+}
+
+impl FromIterator<SingleMessage> for MessageChain {
+    fn from_iter<I: IntoIterator<Item = SingleMessage>>(iter: I) -> Self {
+        MessageChain(iter.into_iter().collect())
+    }
+}
+
+impl Extend<SingleMessage> for MessageChain {
+    fn extend<I: IntoIterator<Item = SingleMessage>>(&mut self, iter: I) {
+        self.0.extend(iter)
+    }
+}
+
+impl IntoIterator for MessageChain {
+    type Item = SingleMessage;
+    type IntoIter = std::vec::IntoIter<SingleMessage>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a MessageChain {
+    type Item = &'a SingleMessage;
+    type IntoIter = std::slice::Iter<'a, SingleMessage>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
 
 pub fn create_plain_message(text: String) -> SingleMessage {
     SingleMessage::Plain { text }
 }
 
 pub fn create_plain_message_chain(text: String) -> MessageChain {
-    let mut message_chain = vec![];
-
-    message_chain.push(create_plain_message(text));
-
-    message_chain
+    MessageChain::from(text)
 }
 
 #[test]
@@ -77,7 +402,7 @@ fn check_message_chain_serialize_result() {
         "{\"type\":\"Plain\",\"text\":\"test\"}"
     );
 
-    let mut message_chain: MessageChain = vec![];
+    let mut message_chain = MessageChain::new();
     message_chain.push(source_message);
     message_chain.push(plain_message);
 
@@ -86,3 +411,107 @@ fn check_message_chain_serialize_result() {
         "[{\"type\":\"Source\",\"id\":20211113,\"time\":20211113},{\"type\":\"Plain\",\"text\":\"test\"}]"
     )
 }
+
+#[test]
+fn check_message_chain_deserialize_result() {
+    let resp = r#"[
+        {"type":"Source","id":7,"time":20211113},
+        {"type":"Quote","id":6,"groupId":20211113,"senderId":20211114,"targetId":20211113,
+            "origin":[{"type":"Plain","text":"hi"}]},
+        {"type":"AtAll"},
+        {"type":"Dice","value":6},
+        {"type":"MusicShare","kind":"NeteaseCloudMusic","title":"title","summary":"summary",
+            "jumpUrl":"jump","pictureUrl":"picture","musicUrl":"music","brief":"brief"},
+        {"type":"Forward","nodeList":[{"senderId":20211114,"time":20211113,"senderName":"木木",
+            "messageChain":[{"type":"Plain","text":"hi"}],"messageId":5}]},
+        {"type":"File","id":"/file","name":"file.txt","size":1024},
+        {"type":"SomeElementFromTheFuture","foo":"bar"}
+    ]"#;
+
+    let message_chain = MessageChain::from(vec![
+        SingleMessage::Source {
+            id: 7,
+            time: 20211113,
+        },
+        SingleMessage::Quote {
+            id: 6,
+            group_id: 20211113,
+            sender_id: 20211114,
+            target_id: 20211113,
+            origin: create_plain_message_chain("hi".to_string()),
+        },
+        SingleMessage::AtAll,
+        SingleMessage::Dice { value: 6 },
+        SingleMessage::MusicShare {
+            kind: "NeteaseCloudMusic".to_string(),
+            title: "title".to_string(),
+            summary: "summary".to_string(),
+            jump_url: "jump".to_string(),
+            picture_url: "picture".to_string(),
+            music_url: "music".to_string(),
+            brief: "brief".to_string(),
+        },
+        SingleMessage::Forward {
+            node_list: vec![ForwardNode {
+                sender_id: Some(20211114),
+                time: Some(20211113),
+                sender_name: Some("木木".to_string()),
+                message_chain: Some(create_plain_message_chain("hi".to_string())),
+                message_id: Some(5),
+            }],
+        },
+        SingleMessage::File {
+            id: "/file".to_string(),
+            name: "file.txt".to_string(),
+            size: 1024,
+        },
+        SingleMessage::Unknown(serde_json::json!({"type":"SomeElementFromTheFuture","foo":"bar"})),
+    ]);
+
+    assert_eq!(
+        serde_json::from_str::<MessageChain>(resp).unwrap(),
+        message_chain
+    );
+
+    assert_eq!(
+        serde_json::to_string(&SingleMessage::AtAll).unwrap(),
+        "{\"type\":\"AtAll\"}"
+    );
+    assert_eq!(
+        serde_json::to_string(&message_chain[7]).unwrap(),
+        "{\"foo\":\"bar\",\"type\":\"SomeElementFromTheFuture\"}"
+    );
+}
+
+#[test]
+fn check_message_chain_builder() {
+    let message_chain = MessageChain::new()
+        .at(20211113)
+        .text(" hello")
+        .face(1)
+        .image_url("https://mirai.host/image.png")
+        .image_bytes(b"wood")
+        .text(" world");
+
+    assert_eq!(
+        serde_json::to_value(&message_chain).unwrap(),
+        serde_json::json!([
+            {"type": "At", "target": 20211113, "display": ""},
+            {"type": "Plain", "text": " hello"},
+            {"type": "Face", "faceId": 1, "name": ""},
+            {"type": "Image", "imageId": null, "url": "https://mirai.host/image.png", "path": null, "base64": null},
+            {"type": "Image", "imageId": null, "url": null, "path": null, "base64": "d29vZA=="},
+            {"type": "Plain", "text": " world"}
+        ])
+    );
+
+    assert_eq!(message_chain.plain_text(), " hello world");
+    assert_eq!(message_chain.mentions(), vec![20211113]);
+    assert_eq!(message_chain.images().len(), 2);
+    assert_eq!(message_chain.source(), None);
+
+    assert_eq!(
+        MessageChain::from("hi"),
+        create_plain_message_chain("hi".to_string())
+    );
+}
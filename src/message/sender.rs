@@ -11,34 +11,118 @@ pub trait Sender {
     fn sender_nickname(&self) -> String;
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+// The names follow mirai, which are kept for compatibility.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub enum Permission {
     OWNER,
     ADMINISTRATOR,
     MEMBER,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Group {
     pub id: i64,
     pub name: String,
     pub permission: Permission,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+// Used both to get and to set the config of a group,
+// the fields which are `None` won't be updated.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confess_talk: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_member_invite: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_approve: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anonymous_chat: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mute_all: Option<bool>,
+}
+
+// The name card and the special title of a group member.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MemberInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub special_title: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct FriendSender {
     pub id: i64,
     pub nickname: String,
     pub remark: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct GroupSender {
     pub id: i64,
     pub member_name: String,
     pub permission: Permission,
     pub group: Group,
+
+    // not carried by some events, e.g. `MemberJoinEvent`
+    #[serde(default)]
+    pub special_title: String,
+    #[serde(default)]
+    pub join_timestamp: i64,
+    #[serde(default)]
+    pub last_speak_timestamp: i64,
+    #[serde(default)]
+    pub mute_time_remaining: i64,
+}
+
+// A group member sending a temp (group private) message.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TempSender {
+    pub id: i64,
+    pub member_name: String,
+    pub permission: Permission,
+    pub group: Group,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct StrangerSender {
+    pub id: i64,
+    pub nickname: String,
+    pub remark: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Sex {
+    Unknown,
+    Male,
+    Female,
+}
+
+// The profile of the bot, a friend, a member or any user.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Profile {
+    pub nickname: String,
+    pub email: String,
+    pub age: i64,
+    pub level: i64,
+    pub sign: String,
+    pub sex: Sex,
+}
+
+// Other clients logged in with the bot account, e.g. the mobile client.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct OtherClient {
+    pub id: i64,
+    pub platform: String,
 }
 
 impl Sender for FriendSender {
@@ -86,6 +170,52 @@ impl Sender for GroupSender {
     }
 }
 
+// The chatroom of a temp message is the group,
+// but it is replied to the sender privately.
+impl Sender for TempSender {
+    fn chatroom_type(&self) -> ChatroomType {
+        ChatroomType::Temp
+    }
+
+    fn chatroom_id(&self) -> i64 {
+        self.group.id
+    }
+
+    fn chatroom_name(&self) -> String {
+        self.group.name.clone()
+    }
+
+    fn sender_id(&self) -> i64 {
+        self.id
+    }
+
+    fn sender_nickname(&self) -> String {
+        self.member_name.clone()
+    }
+}
+
+impl Sender for StrangerSender {
+    fn chatroom_type(&self) -> ChatroomType {
+        ChatroomType::Stranger
+    }
+
+    fn chatroom_id(&self) -> i64 {
+        self.id
+    }
+
+    fn chatroom_name(&self) -> String {
+        "".to_string()
+    }
+
+    fn sender_id(&self) -> i64 {
+        self.id
+    }
+
+    fn sender_nickname(&self) -> String {
+        self.nickname.clone()
+    }
+}
+
 #[test]
 fn check_group_sender_deserialize_result() {
     let resp = r#"
@@ -106,7 +236,7 @@ fn check_group_sender_deserialize_result() {
         }
     }"#;
 
-    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
     struct GroupSenderStruct {
         sender: GroupSender,
     }
@@ -122,6 +252,10 @@ fn check_group_sender_deserialize_result() {
         member_name: "Thungghuan".to_string(),
         permission: Permission::OWNER,
         group,
+        special_title: "".to_string(),
+        join_timestamp: 20211113,
+        last_speak_timestamp: 20211113,
+        mute_time_remaining: 0,
     };
 
     let group_sender_struct = GroupSenderStruct {
@@ -129,7 +263,31 @@ fn check_group_sender_deserialize_result() {
     };
 
     assert_eq!(
-        serde_json::from_str::<GroupSenderStruct>(&resp).unwrap(),
+        serde_json::from_str::<GroupSenderStruct>(resp).unwrap(),
         group_sender_struct
     );
 }
+
+#[test]
+fn check_profile_deserialize_result() {
+    let resp = r#"
+    {
+        "nickname":"Thungghuan",
+        "email":"",
+        "age":18,
+        "level":1,
+        "sign":"木木",
+        "sex":"MALE"
+    }"#;
+
+    let profile = Profile {
+        nickname: "Thungghuan".to_string(),
+        email: "".to_string(),
+        age: 18,
+        level: 1,
+        sign: "木木".to_string(),
+        sex: Sex::Male,
+    };
+
+    assert_eq!(serde_json::from_str::<Profile>(resp).unwrap(), profile);
+}
@@ -0,0 +1,65 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::{Context, Result};
+
+pub type BoxFuture<'a> = Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
+
+// Run around the handlers, registered by `bot.use_middleware(middleware)` for all the events,
+// or by `command.use_middleware(middleware)` for a single command.
+//
+// ```
+// struct Timer;
+//
+// impl Middleware for Timer {
+//     fn call<'a>(&'a self, ctx: Context, next: Next<'a>) -> BoxFuture<'a> {
+//         Box::pin(async move {
+//             let start = Instant::now();
+//             next.run(ctx).await?;
+//             println!("Handled in {:?}", start.elapsed());
+//             Ok(())
+//         })
+//     }
+// }
+// ```
+//
+// The handlers won't be called if `next.run(ctx)` is not called,
+// and the context can be changed before passing to the next one.
+pub trait Middleware: Send + Sync {
+    fn call<'a>(&'a self, ctx: Context, next: Next<'a>) -> BoxFuture<'a>;
+}
+
+// Functions like `fn auth<'a>(ctx: Context, next: Next<'a>) -> BoxFuture<'a>` are middleware too.
+impl<F> Middleware for F
+where
+    F: for<'a> Fn(Context, Next<'a>) -> BoxFuture<'a> + Send + Sync,
+{
+    fn call<'a>(&'a self, ctx: Context, next: Next<'a>) -> BoxFuture<'a> {
+        self(ctx, next)
+    }
+}
+
+type Endpoint<'a> = dyn Fn(Context) -> BoxFuture<'a> + Send + Sync + 'a;
+
+// The rest of the middleware, followed by the handlers.
+pub struct Next<'a> {
+    middlewares: &'a [Arc<dyn Middleware>],
+    endpoint: &'a Endpoint<'a>,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(middlewares: &'a [Arc<dyn Middleware>], endpoint: &'a Endpoint<'a>) -> Self {
+        Next {
+            middlewares,
+            endpoint,
+        }
+    }
+
+    pub fn run(self, ctx: Context) -> BoxFuture<'a> {
+        match self.middlewares.split_first() {
+            Some((middleware, rest)) => middleware.call(ctx, Next::new(rest, self.endpoint)),
+            None => (self.endpoint)(ctx),
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{oneshot, OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinHandle;
+
+use crate::event::Event;
+use crate::message::ChatroomType;
+use crate::{Bot, Context};
+
+type Chat = (ChatroomType, i64);
+
+// The end of the last queued event in each chat, with the generation of the event.
+type Chats = Arc<Mutex<HashMap<Chat, (u64, oneshot::Receiver<()>)>>>;
+
+// Handle each event in a spawned task, with at most `concurrency` of them running at the same time.
+pub(crate) struct Scheduler {
+    semaphore: Arc<Semaphore>,
+    ordered: bool,
+
+    // only used when ordered
+    chats: Chats,
+    generation: u64,
+}
+
+// Held by the task of a queued event, the next event in the chat starts once it's dropped,
+// and the chat is forgotten if there is no next event.
+struct ChatTurn {
+    chats: Chats,
+    chat: Chat,
+    generation: u64,
+    _done: oneshot::Sender<()>,
+}
+
+impl Drop for ChatTurn {
+    fn drop(&mut self) {
+        let mut chats = self.chats.lock().unwrap();
+
+        if matches!(chats.get(&self.chat), Some((generation, _)) if *generation == self.generation)
+        {
+            chats.remove(&self.chat);
+        }
+    }
+}
+
+impl Scheduler {
+    pub fn new(concurrency: usize, ordered: bool) -> Self {
+        Scheduler {
+            semaphore: Arc::new(Semaphore::new(concurrency)),
+            ordered,
+            chats: Default::default(),
+            generation: 0,
+        }
+    }
+
+    async fn acquire(semaphore: Arc<Semaphore>) -> OwnedSemaphorePermit {
+        semaphore
+            .acquire_owned()
+            .await
+            .expect("the semaphore is never closed")
+    }
+
+    pub async fn spawn(&mut self, bot: &Bot, event: Event) -> Option<JoinHandle<()>> {
+        match Context::new(bot.clone(), event) {
+            Ok(ctx) => Some(self.spawn_context(bot, ctx, async {}).await),
+            Err(e) => {
+                eprintln!("[Error] Handling message.\n{}", e);
+                None
+            }
+        }
+    }
+
+    // `then` is run after the handlers, e.g. responding the webhook request.
+    //
+    // Wait until there is a free slot, so that the events are not fetched faster than handled.
+    // An event queued behind another one in the same chat doesn't take a slot until its turn,
+    // so a busy chat won't block the others.
+    pub async fn spawn_context<F>(&mut self, bot: &Bot, ctx: Context, then: F) -> JoinHandle<()>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        // events without a chat, e.g. `BotOnlineEvent`, are never waited
+        let (previous, turn) = if self.ordered && ctx.chatroom_id() != 0 {
+            let (done, end) = oneshot::channel();
+            let chat = (ctx.chatroom_type(), ctx.chatroom_id());
+            self.generation += 1;
+
+            let previous = self
+                .chats
+                .lock()
+                .unwrap()
+                .insert(chat.clone(), (self.generation, end))
+                .map(|(_, end)| end);
+            let turn = ChatTurn {
+                chats: self.chats.clone(),
+                chat,
+                generation: self.generation,
+                _done: done,
+            };
+
+            (previous, Some(turn))
+        } else {
+            (None, None)
+        };
+
+        let permit = match previous {
+            Some(_) => None,
+            None => Some(Scheduler::acquire(self.semaphore.clone()).await),
+        };
+
+        let semaphore = self.semaphore.clone();
+        let bot = bot.clone();
+
+        tokio::spawn(async move {
+            // the previous event is done, or its task is dropped
+            if let Some(previous) = previous {
+                let _ = previous.await;
+            }
+
+            let _permit = match permit {
+                Some(permit) => permit,
+                None => Scheduler::acquire(semaphore).await,
+            };
+
+            bot.dispatch_context(ctx).await;
+            then.await;
+
+            drop(turn);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Scheduler;
+    use crate::bot::tests::{bot, friend_message};
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::message::sender::FriendSender;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::Semaphore;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn busy_chat_does_not_block_others() {
+        // closed by the test to let the waiting handlers go
+        let gate = Arc::new(Semaphore::new(0));
+        let wait = gate.clone();
+
+        let mut bot = bot();
+        bot.register(Command::new("wait", move |_| {
+            let gate = wait.clone();
+            async move {
+                let _ = gate.acquire().await;
+                Ok(())
+            }
+        }));
+        bot.register(Command::new("go", |_| async { Ok(()) }));
+
+        // another friend, whose event is not queued behind the busy chat
+        let event = match friend_message("/go") {
+            Event::FriendMessage { message_chain, .. } => Event::FriendMessage {
+                sender: FriendSender {
+                    id: 20211115,
+                    nickname: "Mumu".to_string(),
+                    remark: "".to_string(),
+                },
+                message_chain,
+            },
+            _ => unreachable!(),
+        };
+
+        let mut scheduler = Scheduler::new(2, true);
+        let mut waits = vec![];
+
+        timeout(Duration::from_secs(1), async {
+            for _ in 0..5 {
+                let task = scheduler.spawn(&bot, friend_message("/wait")).await;
+                waits.push(task.unwrap());
+            }
+
+            let go = scheduler.spawn(&bot, event).await.unwrap();
+            go.await.unwrap();
+        })
+        .await
+        .unwrap();
+
+        gate.close();
+        for task in waits {
+            task.await.unwrap();
+        }
+
+        // the chats are forgotten once their events are all done
+        assert!(scheduler.chats.lock().unwrap().is_empty());
+        assert_eq!(scheduler.generation, 6);
+    }
+}
@@ -2,11 +2,15 @@ use serde::{Deserialize, Serialize};
 use std::{fs::File, io::Read};
 use yaml_rust::{Yaml, YamlLoader};
 
+use crate::adapter::Adapter;
+use crate::{Error, Result};
+
 #[derive(Debug, PartialEq)]
 pub struct BotConfig {
     pub qq: i64,
     pub master_qq: i64,
     pub setting_file: String,
+    pub adapter: Adapter,
 }
 
 #[derive(Debug, PartialEq)]
@@ -14,6 +18,13 @@ struct BotSettings {
     verify_key: String,
     host: String,
     port: String,
+
+    // only exist when the ws adapter is enabled
+    ws_host: Option<String>,
+    ws_port: Option<String>,
+
+    // the port of the first webhook destination
+    webhook_port: Option<String>,
 }
 
 struct ConfigFile {
@@ -22,10 +33,9 @@ struct ConfigFile {
 }
 
 impl ConfigFile {
-    // Panic if error occurs when scanning yaml file.
-    fn load_yaml(&self) -> Yaml {
+    fn load_yaml(&self) -> Result<Yaml> {
         let error_msg = format!(
-            "Error occurs when scanning the yaml file `{}`\nCheck the file first.",
+            "[Error] Error occurs when scanning the yaml file `{}`\nCheck the file first.",
             self.file_name
         );
 
@@ -33,59 +43,150 @@ impl ConfigFile {
     }
 }
 
-fn read_yaml_file(path: &str) -> ConfigFile {
-    let mut file;
-    match File::open(path) {
-        Ok(f) => file = f,
-        Err(e) => panic!("{}", e),
-    }
-
+fn read_yaml_file(path: &str) -> Result<ConfigFile> {
     let mut content = String::new();
-    if let Err(e) = file.read_to_string(&mut content) {
-        panic!("{}", e)
-    }
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut content))
+        .map_err(|e| Error::new(&format!("[Error] Reading the file `{}`.\n{}", path, e)))?;
 
-    ConfigFile {
+    Ok(ConfigFile {
         file_name: path.to_string(),
         content,
-    }
+    })
 }
 
-fn parse_yaml(content: &str, error_msg: &str) -> Yaml {
-    YamlLoader::load_from_str(content).expect(error_msg)[0].clone()
+// An empty file is an empty hash, so that the missing fields are reported by name.
+fn parse_yaml(content: &str, error_msg: &str) -> Result<Yaml> {
+    let docs = YamlLoader::load_from_str(content).map_err(|_| Error::new(error_msg))?;
+
+    Ok(docs
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| Yaml::Hash(Default::default())))
 }
 
-fn load_bot_config(config: Yaml) -> BotConfig {
-    let qq = config["qq"].as_i64().unwrap();
-    let master_qq = config["masterQQ"].as_i64().unwrap();
-    let setting_file = String::from(config["settingFile"].as_str().unwrap());
+fn load_bot_config(config: Yaml) -> Result<BotConfig> {
+    let missing = |field: &str| {
+        Error::new(&format!(
+            "[Error] The `{}` is missing in the bot config.",
+            field
+        ))
+    };
+
+    let qq = config["qq"].as_i64().ok_or_else(|| missing("qq"))?;
+    let master_qq = config["masterQQ"]
+        .as_i64()
+        .ok_or_else(|| missing("masterQQ"))?;
+    let setting_file = config["settingFile"]
+        .as_str()
+        .ok_or_else(|| missing("settingFile"))?
+        .to_string();
 
-    BotConfig {
+    Ok(BotConfig {
         qq,
         master_qq,
         setting_file,
-    }
+        adapter: Adapter::Http,
+    })
+}
+
+// The adapter is chosen in the bot config, while its address
+// comes from the mirai settings.
+fn load_adapter(config: &Yaml, settings: &BotSettings, qq: i64) -> Result<Adapter> {
+    let adapter = match config["adapter"].as_str().unwrap_or("http") {
+        "http" => Adapter::Http,
+        "ws" => match (&settings.ws_host, &settings.ws_port) {
+            (Some(host), Some(port)) => Adapter::Ws(format!(
+                "ws://{}:{}/all?verifyKey={}&qq={}",
+                host, port, settings.verify_key, qq
+            )),
+            _ => {
+                return Err(Error::new(
+                    "[Error] The ws adapter is not configured in the mirai settings.",
+                ))
+            }
+        },
+        "webhook" => {
+            let address = match (config["webhookAddress"].as_str(), &settings.webhook_port) {
+                (Some(address), _) => address.to_string(),
+                (None, Some(port)) => format!("0.0.0.0:{}", port),
+                _ => {
+                    return Err(Error::new(
+                        "[Error] The webhook adapter is not configured in the mirai settings.",
+                    ))
+                }
+            };
+            let reply = config["webhookReply"].as_bool().unwrap_or(false);
+
+            Adapter::Webhook { address, reply }
+        }
+        adapter => {
+            return Err(Error::new(&format!(
+                "[Error] Invalid adapter: received `{}`, expected `http`, `ws` or `webhook`.",
+                adapter
+            )))
+        }
+    };
+
+    Ok(adapter)
 }
 
-fn load_bot_settings(config: Yaml) -> BotSettings {
-    let verify_key = config["verifyKey"].as_str().unwrap().to_string();
-    let host = config["adapterSettings"]["http"]["host"]
+// The http adapter is always required, which is used to get the session.
+fn load_bot_settings(config: Yaml) -> Result<BotSettings> {
+    let verify_key = config["verifyKey"]
         .as_str()
-        .unwrap()
+        .ok_or_else(|| Error::new("[Error] The `verifyKey` is missing in the mirai settings."))?
         .to_string();
-    let port = config["adapterSettings"]["http"]["port"]
+
+    let http = &config["adapterSettings"]["http"];
+    let (host, port) = match (http["host"].as_str(), http["port"].as_i64()) {
+        (Some(host), Some(port)) => (host.to_string(), port.to_string()),
+        _ => {
+            return Err(Error::new(
+                "[Error] The http adapter is not configured in the mirai settings.",
+            ))
+        }
+    };
+
+    let ws_host = config["adapterSettings"]["ws"]["host"]
+        .as_str()
+        .map(|host| host.to_string());
+    let ws_port = config["adapterSettings"]["ws"]["port"]
         .as_i64()
-        .unwrap()
-        .to_string();
+        .map(|port| port.to_string());
+
+    let webhook_port = config["adapterSettings"]["webhook"]["destinations"][0]
+        .as_str()
+        .map(destination_port);
 
-    BotSettings {
+    Ok(BotSettings {
         verify_key,
         host,
         port,
+        ws_host,
+        ws_port,
+        webhook_port,
+    })
+}
+
+// `http://127.0.0.1:8080/path` => `8080`
+fn destination_port(destination: &str) -> String {
+    let authority = destination
+        .split("://")
+        .last()
+        .unwrap_or("")
+        .split('/')
+        .next()
+        .unwrap_or("");
+
+    match authority.rsplit_once(':') {
+        Some((_, port)) => port.to_string(),
+        None if destination.starts_with("https://") => "443".to_string(),
+        None => "80".to_string(),
     }
 }
 
-async fn get_session(base_url: &str, verify_key: &str) -> String {
+async fn get_session(base_url: &str, verify_key: &str) -> Result<String> {
     #[derive(Deserialize, Debug)]
     struct VerifyResponse {
         session: String,
@@ -107,27 +208,32 @@ async fn get_session(base_url: &str, verify_key: &str) -> String {
         .post(String::from(base_url) + "/verify")
         .json(&params)
         .send()
-        .await
-        .unwrap()
+        .await?
         .json::<VerifyResponse>()
-        .await
-        .unwrap();
+        .await?;
 
-    resp.session
+    Ok(resp.session)
 }
 
-pub async fn init(path: &str) -> (BotConfig, String, String) {
-    let config = load_bot_config(read_yaml_file(path).load_yaml());
-    let settings = load_bot_settings(read_yaml_file(&config.setting_file).load_yaml());
+// Return the config, the session and the base url of mirai.
+pub async fn init(path: &str) -> Result<(BotConfig, String, String)> {
+    let config_yaml = read_yaml_file(path)?.load_yaml()?;
+    let mut config = load_bot_config(config_yaml.clone())?;
+    let settings = load_bot_settings(read_yaml_file(&config.setting_file)?.load_yaml()?)?;
+    config.adapter = load_adapter(&config_yaml, &settings, config.qq)?;
     let base_url = String::from("http://") + &settings.host + ":" + &settings.port;
-    let session = get_session(&base_url, &settings.verify_key).await;
+    let session = get_session(&base_url, &settings.verify_key).await?;
 
-    (config, session, base_url)
+    Ok((config, session, base_url))
 }
 
 #[cfg(test)]
 mod test_utils {
-    use super::{load_bot_config, load_bot_settings, parse_yaml, BotConfig, BotSettings};
+    use super::{
+        destination_port, load_adapter, load_bot_config, load_bot_settings, parse_yaml, BotConfig,
+        BotSettings,
+    };
+    use crate::adapter::Adapter;
 
     #[test]
     fn check_load_bot_config() {
@@ -139,15 +245,19 @@ masterQQ: 10000000
 # Path to the `settings.yml` file for mirai
 settingFile: 'config/settings.yml'
         "#;
-        let config = parse_yaml(&config_file_string, "test error");
+        let config = parse_yaml(config_file_string, "test error").unwrap();
 
         let bot_config = BotConfig {
             qq: 10000000,
             master_qq: 10000000,
             setting_file: "config/settings.yml".to_string(),
+            adapter: Adapter::Http,
         };
 
-        assert_eq!(load_bot_config(config), bot_config);
+        assert_eq!(load_bot_config(config).unwrap(), bot_config);
+
+        let config = parse_yaml("qq: 10000000", "test error").unwrap();
+        assert!(load_bot_config(config).is_err());
     }
 
     #[test]
@@ -173,16 +283,102 @@ adapterSettings:
         host: mirai.host
         port: 80
         reservedSyncId: -1
+
+    webhook:
+        destinations:
+            - "http://bot.host:8080/"
         "#;
 
-        let config = parse_yaml(&settings_file_string, "test error");
+        let config = parse_yaml(settings_file_string, "test error").unwrap();
 
         let bot_settings = BotSettings {
             verify_key: "verify-key".to_string(),
             host: "mirai.host".to_string(),
             port: "80".to_string(),
+            ws_host: Some("mirai.host".to_string()),
+            ws_port: Some("80".to_string()),
+            webhook_port: Some("8080".to_string()),
+        };
+
+        assert_eq!(load_bot_settings(config).unwrap(), bot_settings);
+    }
+
+    #[test]
+    fn check_load_bot_settings_without_http() {
+        let settings_file_string = r#"
+verifyKey: verify-key
+adapterSettings:
+    ws:
+        host: mirai.host
+        port: 80
+        "#;
+
+        let config = parse_yaml(settings_file_string, "test error").unwrap();
+        assert!(load_bot_settings(config).is_err());
+    }
+
+    #[test]
+    fn check_load_adapter() {
+        let settings = BotSettings {
+            verify_key: "verify-key".to_string(),
+            host: "mirai.host".to_string(),
+            port: "80".to_string(),
+            ws_host: Some("mirai.host".to_string()),
+            ws_port: Some("8080".to_string()),
+            webhook_port: Some("8081".to_string()),
+        };
+
+        let config = parse_yaml("qq: 10000000", "test error").unwrap();
+        assert_eq!(
+            load_adapter(&config, &settings, 10000000).unwrap(),
+            Adapter::Http
+        );
+
+        let config = parse_yaml("qq: 10000000\nadapter: ws", "test error").unwrap();
+        assert_eq!(
+            load_adapter(&config, &settings, 10000000).unwrap(),
+            Adapter::Ws("ws://mirai.host:8080/all?verifyKey=verify-key&qq=10000000".to_string())
+        );
+
+        let config = parse_yaml("qq: 10000000\nadapter: webhook", "test error").unwrap();
+        assert_eq!(
+            load_adapter(&config, &settings, 10000000).unwrap(),
+            Adapter::Webhook {
+                address: "0.0.0.0:8081".to_string(),
+                reply: false
+            }
+        );
+
+        let config = parse_yaml(
+            "qq: 10000000\nadapter: webhook\nwebhookAddress: 127.0.0.1:9000\nwebhookReply: true",
+            "test error",
+        )
+        .unwrap();
+        assert_eq!(
+            load_adapter(&config, &settings, 10000000).unwrap(),
+            Adapter::Webhook {
+                address: "127.0.0.1:9000".to_string(),
+                reply: true
+            }
+        );
+
+        // the ws adapter is chosen, but not enabled in mirai
+        let settings = BotSettings {
+            ws_host: None,
+            ws_port: None,
+            ..settings
         };
+        let config = parse_yaml("qq: 10000000\nadapter: ws", "test error").unwrap();
+        assert!(load_adapter(&config, &settings, 10000000).is_err());
+
+        let config = parse_yaml("qq: 10000000\nadapter: tcp", "test error").unwrap();
+        assert!(load_adapter(&config, &settings, 10000000).is_err());
+    }
 
-        assert_eq!(load_bot_settings(config), bot_settings);
+    #[test]
+    fn check_destination_port() {
+        assert_eq!(destination_port("http://127.0.0.1:8080/events"), "8080");
+        assert_eq!(destination_port("http://bot.host/"), "80");
+        assert_eq!(destination_port("https://bot.host"), "443");
     }
 }
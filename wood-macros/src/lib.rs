@@ -0,0 +1,137 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::parse::Parser;
+use syn::{parse_macro_input, Expr, ExprArray, ExprPath, FnArg, ItemFn, Lit, LitStr, Visibility};
+
+// Turn an async handler into a function returning `wood::command::Command`,
+// which is registered by `bot.register(handler())`.
+//
+// ```
+// #[wood::command(name = "ban", aliases = ["b"], desc = "Ban a member.")]
+// async fn ban(ctx: Context, (member, duration): (At, Duration)) -> Result<()> {
+//     ...
+// }
+// ```
+//
+// The second parameter is optional, which is parsed from the arguments
+// and used to generate the usage of the command.
+//
+// Subcommands are other functions with `#[wood::command]`,
+// e.g. `#[wood::command(name = "todo", subcommands = [todo_add, todo_list])]`.
+#[proc_macro_attribute]
+pub fn command(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let handler = parse_macro_input!(item as ItemFn);
+
+    let mut name = None;
+    let mut aliases = vec![];
+    let mut desc = None;
+    let mut usage = None;
+    let mut subcommands = vec![];
+
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("name") {
+            name = Some(meta.value()?.parse::<LitStr>()?);
+        } else if meta.path.is_ident("desc") {
+            desc = Some(meta.value()?.parse::<LitStr>()?);
+        } else if meta.path.is_ident("usage") {
+            usage = Some(meta.value()?.parse::<LitStr>()?);
+        } else if meta.path.is_ident("aliases") {
+            let array = meta.value()?.parse::<ExprArray>()?;
+
+            for elem in array.elems {
+                match elem {
+                    Expr::Lit(syn::ExprLit {
+                        lit: Lit::Str(alias),
+                        ..
+                    }) => aliases.push(alias),
+                    elem => return Err(syn::Error::new_spanned(elem, "expected a string")),
+                }
+            }
+        } else if meta.path.is_ident("subcommands") {
+            let array = meta.value()?.parse::<ExprArray>()?;
+
+            for elem in array.elems {
+                match elem {
+                    Expr::Path(subcommand) => subcommands.push(subcommand),
+                    elem => return Err(syn::Error::new_spanned(elem, "expected a function")),
+                }
+            }
+        } else {
+            return Err(meta.error("expected `name`, `aliases`, `desc`, `usage` or `subcommands`"));
+        }
+
+        Ok(())
+    });
+
+    if let Err(e) = parser.parse(attr) {
+        return e.to_compile_error().into();
+    }
+
+    match expand(handler, name, aliases, desc, usage, subcommands) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(
+    handler: ItemFn,
+    name: Option<LitStr>,
+    aliases: Vec<LitStr>,
+    desc: Option<LitStr>,
+    usage: Option<LitStr>,
+    subcommands: Vec<ExprPath>,
+) -> syn::Result<TokenStream2> {
+    if handler.sig.asyncness.is_none() {
+        return Err(syn::Error::new_spanned(
+            handler.sig.fn_token,
+            "the command handler must be async",
+        ));
+    }
+
+    let ident = handler.sig.ident.clone();
+    let vis = handler.vis.clone();
+    let name = name.unwrap_or_else(|| LitStr::new(&ident.to_string(), ident.span()));
+
+    let command = match handler.sig.inputs.len() {
+        1 => quote! { ::wood::command::Command::new(#name, #ident) },
+        2 => {
+            let args = match &handler.sig.inputs[1] {
+                FnArg::Typed(arg) => &arg.ty,
+                FnArg::Receiver(receiver) => {
+                    return Err(syn::Error::new_spanned(receiver, "unexpected `self`"))
+                }
+            };
+
+            quote! { ::wood::command::Command::with_args::<#args, _, _>(#name, #ident) }
+        }
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &handler.sig.inputs,
+                "expected `(ctx: Context)` or `(ctx: Context, args: T)`",
+            ))
+        }
+    };
+
+    let aliases = if aliases.is_empty() {
+        quote! {}
+    } else {
+        quote! { .aliases(&[#(#aliases),*]) }
+    };
+    let desc = desc.map(|desc| quote! { .desc(#desc) });
+    let usage = usage.map(|usage| quote! { .usage(#usage) });
+    let subcommands = subcommands
+        .iter()
+        .map(|subcommand| quote! { .subcommand(#subcommand()) });
+
+    let mut inner = handler;
+    inner.vis = Visibility::Inherited;
+
+    Ok(quote! {
+        #vis fn #ident() -> ::wood::command::Command {
+            #inner
+
+            #command #aliases #desc #usage #(#subcommands)*
+        }
+    })
+}